@@ -158,14 +158,16 @@ show_usage() {
     echo
     echo "     For Bash/Zsh (~/.bashrc or ~/.zshrc):"
     echo '     z() {'
-    echo '         if [[ "$*" == *"--help"* ]] || [[ "$*" == *"-h"* ]] || [[ "$*" == *"-l"* ]] || [[ "$*" == *"-r"* ]] || [[ "$*" == *"-t"* ]] || [[ "$*" == *"-c"* ]] || [[ "$*" == *"-e"* ]] || [[ "$*" == *"-x"* ]] || [[ "$*" == *"--add"* ]]; then'
-    echo '             command zjyo "$@"'
-    echo '             return'
-    echo '         fi'
+    echo '         local arg'
+    echo '         for arg in "$@"; do'
+    echo '             case "$arg" in'
+    echo '                 --help|-h|-l|-r|-t|-c|-e|-x|--add) command zjyo "$@"; return ;;'
+    echo '             esac'
+    echo '         done'
     echo '         if [[ $# -eq 0 ]]; then'
     echo '             command zjyo'
     echo '         else'
-    echo '             local result=$(command zjyo -e "$*")'
+    echo '             local result=$(command zjyo -e --learn -- "$*")'
     echo '             if [[ -n $result ]]; then'
     echo '                 cd "$result"'
     echo '             fi'
@@ -182,7 +184,7 @@ show_usage() {
     echo '         if test (count $argv) -eq 0'
     echo '             command zjyo'
     echo '         else'
-    echo '             set result (command zjyo -e (string join " " $argv))'
+    echo '             set result (command zjyo -e --learn -- (string join " " $argv))'
     echo '             if test -n "$result"'
     echo '                 cd "$result"'
     echo '             end'
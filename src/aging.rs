@@ -0,0 +1,123 @@
+use crate::database::ZDatabase;
+use crate::entry::DirEntry;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Entries decayed below this rank are dropped. It is lower than the rank of 1
+// z.sh keeps, so a single short dwell-weighted visit survives a while.
+pub const DECAY_FLOOR: f64 = 0.1;
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+// Source of the current time, so aging can be reproduced in tests.
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+// A clock stopped at a fixed Unix timestamp.
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aging {
+    // z.sh aging: multiply every rank by 0.99 once their sum exceeds 9000
+    Threshold,
+    // Halve every rank each time this many seconds pass
+    HalfLife(f64),
+}
+
+impl Aging {
+    // The strategy configured through `_Z_HALF_LIFE`, defaulting to z.sh's.
+    pub fn from_env() -> Self {
+        match env::var("_Z_HALF_LIFE") {
+            Ok(value) => parse_half_life(&value).unwrap_or_else(|err| {
+                eprintln!("z: {} in _Z_HALF_LIFE", err);
+                Aging::Threshold
+            }),
+            Err(_) => Aging::Threshold,
+        }
+    }
+}
+
+// Parses a half-life given in days, which must be a positive finite number.
+pub fn parse_half_life(value: &str) -> Result<Aging, String> {
+    match value.parse::<f64>() {
+        Ok(days) if days.is_finite() && days > 0.0 => Ok(Aging::HalfLife(days * SECONDS_PER_DAY)),
+        _ => Err(format!(
+            "invalid half-life: {} (expected a number of days above 0)",
+            value
+        )),
+    }
+}
+
+// The factor ranks are multiplied by after `elapsed` seconds.
+pub fn decay_factor(elapsed: u64, half_life: f64) -> f64 {
+    0.5f64.powf(elapsed as f64 / half_life)
+}
+
+impl ZDatabase {
+    pub fn decay_file(&self) -> PathBuf {
+        self.sidecar_file("decay")
+    }
+
+    // Reads when the saved ranks were last decayed.
+    pub fn load_decay(&mut self) {
+        self.decayed_at = fs::read_to_string(self.decay_file())
+            .ok()
+            .and_then(|content| content.trim().parse().ok());
+    }
+
+    // Written along with the datafile, so that the time stays in step with
+    // the saved ranks.
+    pub fn save_decay(&self) -> io::Result<()> {
+        match self.decayed_at {
+            Some(time) => {
+                let mut file = File::create(self.decay_file())?;
+                writeln!(file, "{}", time)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Decays every rank for the time elapsed since the previous decay, which
+    // is applied lazily whenever the database changes. The first call only
+    // starts the clock. Nothing is written until the database is saved.
+    // Returns the entries that fell below DECAY_FLOOR; weighted entries are
+    // kept like with threshold aging.
+    pub fn decay(&mut self, half_life: f64, now: u64) -> Vec<DirEntry> {
+        let mut aged_out = Vec::new();
+        if let Some(decayed_at) = self.decayed_at {
+            let factor = decay_factor(now.saturating_sub(decayed_at), half_life);
+            self.entries.retain(|_, entry| {
+                entry.rank *= factor;
+                let keep = entry.rank >= DECAY_FLOOR || entry.weight != 1.0;
+                if !keep {
+                    aged_out.push(entry.clone());
+                }
+                keep
+            });
+        }
+        self.decayed_at = Some(now);
+        aged_out.sort_by(|a, b| a.path.cmp(&b.path));
+        aged_out
+    }
+}
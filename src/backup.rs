@@ -0,0 +1,124 @@
+use crate::database::ZDatabase;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const DEFAULT_MAX_BACKUPS: usize = 5;
+
+// Sidecars rewritten from the entries, which lose the data of removed paths
+// and so are backed up together with the datafile.
+const ENTRY_SIDECARS: [&str; 4] = ["hist", "tags", "weights", "decay"];
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub index: usize,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub entries: usize,
+}
+
+pub fn max_backups() -> usize {
+    env::var("_Z_BACKUPS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_BACKUPS)
+}
+
+impl ZDatabase {
+    pub fn backup_file(&self, index: usize) -> PathBuf {
+        self.sidecar_file(&format!("bak.{}", index))
+    }
+
+    // The files making up a backup, paired with the live files they were
+    // copied from: the datafile first, then its entry sidecars as
+    // .bak.N.tags and so on.
+    fn backup_set(&self, index: usize) -> Vec<(PathBuf, PathBuf)> {
+        let mut files = vec![(self.data_file.clone(), self.backup_file(index))];
+        for extension in ENTRY_SIDECARS {
+            files.push((
+                self.sidecar_file(extension),
+                self.sidecar_file(&format!("bak.{}.{}", index, extension)),
+            ));
+        }
+        files
+    }
+
+    // Copies the datafile and its entry sidecars as they are on disk to
+    // .bak.1, shifting older backups up and dropping the oldest one.
+    pub fn snapshot(&self) {
+        let max = max_backups();
+        if max == 0 || !self.data_file.exists() {
+            return;
+        }
+
+        for (_, backup) in self.backup_set(max) {
+            fs::remove_file(backup).ok();
+        }
+        for index in (1..max).rev() {
+            for ((_, from), (_, to)) in self
+                .backup_set(index)
+                .into_iter()
+                .zip(self.backup_set(index + 1))
+            {
+                if from.exists() {
+                    fs::rename(&from, to).ok();
+                }
+            }
+        }
+        for (file, backup) in self.backup_set(1) {
+            if file.exists() {
+                fs::copy(&file, backup).ok();
+            }
+        }
+    }
+
+    pub fn backups(&self) -> Vec<Backup> {
+        (1..)
+            .map(|index| (index, self.backup_file(index)))
+            .take_while(|(_, path)| path.exists())
+            .map(|(index, path)| Backup {
+                index,
+                modified: fs::metadata(&path).and_then(|m| m.modified()).ok(),
+                entries: ZDatabase::read_entries(&path).map_or(0, |e| e.len()),
+                path,
+            })
+            .collect()
+    }
+
+    // Restores the most recent backup and reloads the entries from it.
+    // Returns false when there is nothing to undo.
+    pub fn undo(&mut self) -> io::Result<bool> {
+        let latest = self.backup_file(1);
+        if !latest.exists() {
+            return Ok(false);
+        }
+
+        // Sidecars that didn't exist when the backup was taken go away
+        for (file, backup) in self.backup_set(1) {
+            if backup.exists() {
+                fs::rename(&backup, &file)?;
+            } else if file.exists() {
+                fs::remove_file(&file)?;
+            }
+        }
+        let mut index = 2;
+        while self.backup_file(index).exists() {
+            for ((_, from), (_, to)) in self
+                .backup_set(index)
+                .into_iter()
+                .zip(self.backup_set(index - 1))
+            {
+                if from.exists() {
+                    fs::rename(&from, to)?;
+                }
+            }
+            index += 1;
+        }
+
+        self.entries.clear();
+        self.load();
+        Ok(true)
+    }
+}
@@ -1,7 +1,26 @@
 use crate::database::ZDatabase;
-use clap::{Arg, Command};
+use crate::dwell;
+use crate::entry::DirEntry;
+use crate::export::{self, ExportFormat};
+use crate::fold;
+use crate::import::{self, ImportMode, ImportReport};
+use crate::learn::Learned;
+use crate::matcher;
+use crate::merge::{MergePolicy, MergeReport};
+use crate::pins::Pins;
+use crate::query::{self, Query, SearchResult};
+use crate::scorer::{self, Rank, Recent, Scorer};
+use crate::suggest;
+use crate::tags;
+use crate::vcs::{self, Scope};
+use crate::weight;
+use clap::{Arg, ArgMatches, Command};
+use std::collections::BTreeMap;
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn run() {
     let matches = Command::new("zjyo")
@@ -35,28 +54,341 @@ pub fn run() {
                 .help("Restrict matches to subdirectories of current directory")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("scope")
+                .long("scope")
+                .help("Restrict matches to the current directory, its repository or the nearest marker ancestor")
+                .value_parser(vcs::SCOPES.to_vec()),
+        )
+        .arg(
+            Arg::new("marker")
+                .long("marker")
+                .value_name("FILE")
+                .help("Marker file that ends the search for the --scope marker root")
+                .action(clap::ArgAction::Append),
+        )
         .arg(
             Arg::new("echo")
                 .short('e')
                 .help("Echo the best match, don't cd to it")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("learn")
+                .long("learn")
+                .help("With -e, remember the match as chosen for the pattern (used by the shell wrapper)")
+                .requires("echo")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("exclude")
                 .short('x')
                 .help("Remove the current directory from the datafile")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("scorer")
+                .long("scorer")
+                .help("Scoring strategy used to order matches")
+                .value_parser(scorer::SCORERS.to_vec()),
+        )
+        .arg(
+            Arg::new("match")
+                .long("match")
+                .help("Where pattern words may match: anywhere, prefer-basename or basename")
+                .value_parser(matcher::MATCH_MODES.to_vec()),
+        )
+        .arg(
+            Arg::new("fuzzy")
+                .long("fuzzy")
+                .help("Match pattern words as subsequences, ranked by match quality")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore-accents")
+                .long("ignore-accents")
+                .help("Match letters regardless of accents, so cafe matches café")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("autocorrect")
+                .long("autocorrect")
+                .help("Jump to the only close suggestion when nothing matches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("repo")
+                .long("repo")
+                .help("Only match repository roots (.git, .hg or .jj)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("repo-boost")
+                .long("repo-boost")
+                .value_name("FACTOR")
+                .help("Multiply the scores of repository roots by FACTOR")
+                .value_parser(vcs::parse_repo_boost),
+        )
+        .arg(
+            Arg::new("proximity")
+                .long("proximity")
+                .value_name("WEIGHT")
+                .help("Boost candidates sharing a path prefix with the current directory by up to 1 + WEIGHT")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("include-cwd")
+                .long("include-cwd")
+                .help("Allow the current directory as the jump target")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .help("Explain how each candidate was scored instead of jumping")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("add")
                 .long("add")
                 .help("Add current directory to database")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("leave")
+                .long("leave")
+                .value_name("DIR")
+                .help("Record leaving DIR, weighting its rank by the time spent there")
+                .requires("dwell"),
+        )
+        .arg(
+            Arg::new("dwell")
+                .long("dwell")
+                .value_name("SECONDS")
+                .help("Seconds spent in the directory given to --leave")
+                .value_parser(clap::value_parser!(u64))
+                .requires("leave"),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Merge other datafiles into the database")
+                .arg(
+                    Arg::new("files")
+                        .help("Datafiles to merge")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("policy")
+                        .long("policy")
+                        .help("How to combine ranks: sum, max or weighted")
+                        .default_value("sum"),
+                )
+                .arg(
+                    Arg::new("weight")
+                        .long("weight")
+                        .help("Weight applied to incoming ranks with --policy weighted")
+                        .value_parser(weight::parse_weight),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Show what would change without writing the datafile")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Import history from another directory jumper")
+                .arg(
+                    Arg::new("format")
+                        .help("Format of the history to import")
+                        .required(true)
+                        .value_parser(["zoxide", "autojump", "fasd"]),
+                )
+                .arg(Arg::new("file").help(
+                    "File to import from, or - for stdin (defaults to the tool's own datafile)",
+                ))
+                .arg(
+                    Arg::new("replace")
+                        .long("replace")
+                        .help("Replace existing entries instead of merging with them")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Show what would change without writing the datafile")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export the database for analysis or other tools")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["json", "csv", "tsv", "zoxide"])
+                        .default_value("json"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write to a file instead of stdout"),
+                ),
+        )
+        .subcommand(Command::new("undo").about("Restore the datafile from the most recent backup"))
+        .subcommand(Command::new("backups").about("List datafile backups"))
+        .subcommand(
+            Command::new("pin")
+                .about("Pin an alias to a directory, or list pinned aliases")
+                .arg(Arg::new("alias").help("Keyword that always jumps to the directory"))
+                .arg(
+                    Arg::new("path")
+                        .help("Directory to pin (defaults to the current directory)"),
+                ),
+        )
+        .subcommand(
+            Command::new("unpin")
+                .about("Remove a pinned alias")
+                .arg(Arg::new("alias").help("Alias to remove").required(true)),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Tag a directory, adding it to the database if needed")
+                .arg(
+                    Arg::new("tags")
+                        .help("Tags to add, with or without a leading @")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("path")
+                        .short('p')
+                        .long("path")
+                        .help("Directory to tag (defaults to the current directory)"),
+                ),
+        )
+        .subcommand(
+            Command::new("untag")
+                .about("Remove tags from a directory")
+                .arg(
+                    Arg::new("tags")
+                        .help("Tags to remove, with or without a leading @")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("path")
+                        .short('p')
+                        .long("path")
+                        .help("Directory to untag (defaults to the current directory)"),
+                ),
+        )
+        .subcommand(
+            Command::new("tags")
+                .about("List tags, or the directories with a tag")
+                .arg(Arg::new("tag").help("Tag whose directories to list")),
+        )
+        .subcommand(
+            Command::new("weight")
+                .about("Boost or demote a directory with a persistent score multiplier")
+                .arg(
+                    Arg::new("factor")
+                        .help("Multiplier such as 3 to boost or 0.1 to demote, 1 to reset (lists weights if omitted)")
+                        .allow_negative_numbers(true),
+                )
+                .arg(
+                    Arg::new("path")
+                        .short('p')
+                        .long("path")
+                        .help("Directory to weight (defaults to the current directory)"),
+                ),
+        )
+        .subcommand(
+            Command::new("learned")
+                .about("List the directories chosen for earlier queries")
+                .arg(
+                    Arg::new("clear")
+                        .long("clear")
+                        .help("Forget all learned selections")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show visit statistics")
+                .arg(
+                    Arg::new("count")
+                        .short('n')
+                        .help("Number of most visited directories to show")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                ),
+        )
+        .args_conflicts_with_subcommands(true)
         .get_matches();
 
     let mut db = ZDatabase::new();
 
+    match matches.subcommand() {
+        Some(("merge", sub_matches)) => {
+            run_merge(&mut db, sub_matches);
+            return;
+        }
+        Some(("import", sub_matches)) => {
+            run_import(&mut db, sub_matches);
+            return;
+        }
+        Some(("export", sub_matches)) => {
+            run_export(&db, sub_matches);
+            return;
+        }
+        Some(("undo", _)) => {
+            run_undo(&mut db);
+            return;
+        }
+        Some(("backups", _)) => {
+            run_backups(&db);
+            return;
+        }
+        Some(("pin", sub_matches)) => {
+            run_pin(&db, sub_matches);
+            return;
+        }
+        Some(("unpin", sub_matches)) => {
+            run_unpin(&db, sub_matches);
+            return;
+        }
+        Some(("tag", sub_matches)) => {
+            run_tag(&mut db, sub_matches);
+            return;
+        }
+        Some(("untag", sub_matches)) => {
+            run_untag(&mut db, sub_matches);
+            return;
+        }
+        Some(("tags", sub_matches)) => {
+            run_tags(&db, sub_matches);
+            return;
+        }
+        Some(("weight", sub_matches)) => {
+            run_weight(&mut db, sub_matches);
+            return;
+        }
+        Some(("learned", sub_matches)) => {
+            run_learned(&db, sub_matches);
+            return;
+        }
+        Some(("stats", sub)) => {
+            run_stats(&db, *sub.get_one::<usize>("count").unwrap());
+            return;
+        }
+        _ => {}
+    }
+
     if matches.get_flag("add") {
         if let Ok(current_dir) = env::current_dir() {
             db.add(&current_dir.to_string_lossy());
@@ -64,6 +396,12 @@ pub fn run() {
         return;
     }
 
+    if let Some(path) = matches.get_one::<String>("leave") {
+        let dwell = *matches.get_one::<u64>("dwell").unwrap();
+        db.leave(path, dwell, dwell::min_dwell());
+        return;
+    }
+
     if matches.get_flag("exclude") {
         if let Ok(current_dir) = env::current_dir() {
             db.remove(&current_dir.to_string_lossy());
@@ -76,47 +414,760 @@ pub fn run() {
         .get_one::<String>("pattern")
         .unwrap_or(&empty_string);
 
-    if pattern.is_empty() && !matches.get_flag("list") {
+    if pattern.is_empty() && !matches.get_flag("list") && !matches.get_flag("explain") {
         eprintln!("Usage: zjyo [options] <pattern>");
         return;
     }
 
-    let current_dir = if matches.get_flag("current") {
-        env::current_dir()
-            .ok()
-            .map(|p| p.to_string_lossy().to_string())
-    } else {
-        None
+    let scope = match matches.get_one::<String>("scope") {
+        Some(scope) => Some(scope.parse::<Scope>().unwrap()),
+        None if matches.get_flag("current") => Some(Scope::Cwd),
+        None => None,
+    };
+    // Pinned aliases win regardless of frecency
+    let pins = db.load_pins();
+    if let Some(pinned) = pins.resolve(pattern).map(str::to_string) {
+        if !matches.get_flag("list") {
+            jump_to_pin(&mut db, pattern.trim(), &pinned, &matches);
+            return;
+        }
+    }
+
+    let learned = db.load_learned();
+    let current_dir = match scope {
+        Some(scope) => match scope_dir(scope, &matches) {
+            Some(dir) => Some(dir),
+            None => std::process::exit(1),
+        },
+        None => None,
     };
 
-    let matching_dirs = if matches.get_flag("rank") {
-        db.find_by_rank(pattern)
+    // Jumping to the directory we are already in does nothing, so it is
+    // skipped in favor of the next-best candidate. Listings keep it.
+    let cwd = env::current_dir()
+        .ok()
+        .map(|p| p.to_string_lossy().to_string());
+    let working_dir = cwd.clone().filter(|_| {
+        !matches.get_flag("list") && !matches.get_flag("include-cwd") && query::cwd_excluded()
+    });
+
+    let scorer: Box<dyn Scorer> = if matches.get_flag("rank") {
+        Box::new(Rank)
     } else if matches.get_flag("time") {
-        db.find_by_time(pattern)
+        Box::new(Recent)
+    } else if let Some(name) = matches.get_one::<String>("scorer") {
+        scorer::scorer_by_name(name).unwrap()
     } else {
-        db.find_matches(pattern, current_dir.as_deref())
+        scorer::default_scorer()
+    };
+
+    let match_mode = match matches.get_one::<String>("match") {
+        Some(mode) => mode.parse().unwrap(),
+        None => matcher::default_match_mode(),
+    };
+
+    let query = Query {
+        current_dir: current_dir.as_deref(),
+        match_mode,
+        fuzzy: matches.get_flag("fuzzy") || matcher::fuzzy_enabled(),
+        ignore_accents: matches.get_flag("ignore-accents") || fold::accents_ignored(),
+        exclude_dir: working_dir.as_deref(),
+        repo_boost: matches
+            .get_one::<f64>("repo-boost")
+            .copied()
+            .or_else(vcs::default_repo_boost),
+        learned: Some(&learned),
+        repo_only: matches.get_flag("repo"),
+        near_dir: cwd.as_deref(),
+        proximity_weight: matches
+            .get_one::<f64>("proximity")
+            .copied()
+            .unwrap_or_else(query::default_proximity_weight),
+        ..Query::new(pattern, scorer.as_ref())
     };
+    let result = db.search(&query);
 
+    if matches.get_flag("explain") {
+        print_explanation(&result, scorer.as_ref());
+        return;
+    }
+
+    let candidates = result.candidates;
     if matches.get_flag("list") {
-        for entry in &matching_dirs {
-            println!("{:<10} {:<10} {}", entry.frecency(), entry.rank, entry.path);
+        if let Some(pinned) = pins.resolve(pattern) {
+            println!(
+                "{:<10} {:<10} {}{}",
+                "pinned",
+                "-",
+                pinned,
+                list_labels(&db, &pins, pinned)
+            );
+        }
+        for candidate in &candidates {
+            if pins.resolve(pattern) == Some(candidate.entry.path.as_str()) {
+                continue;
+            }
+            println!(
+                "{:<10.0} {:<10} {}{}",
+                candidate.score,
+                candidate.entry.rank,
+                candidate.entry.path,
+                list_labels(&db, &pins, &candidate.entry.path)
+            );
         }
         return;
     }
 
-    if let Some(best_match) = matching_dirs.first() {
-        if matches.get_flag("echo") {
+    let mut best_match = candidates.first().map(|candidate| candidate.entry.clone());
+    if best_match.is_none() && !pattern.is_empty() {
+        let suggestions = db.suggest(&query);
+        let autocorrect = matches.get_flag("autocorrect") || suggest::autocorrect_enabled();
+        match suggestions.as_slice() {
+            [only] if autocorrect => {
+                eprintln!(
+                    "z: no matches found for: {}, using: {}",
+                    pattern, only.pattern
+                );
+                best_match = Some(only.target.clone());
+            }
+            _ => {
+                eprintln!("z: no matches found for: {}", pattern);
+                if !suggestions.is_empty() {
+                    let patterns: Vec<&str> =
+                        suggestions.iter().map(|s| s.pattern.as_str()).collect();
+                    eprintln!("z: did you mean: {}", patterns.join(", "));
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(best_match) = best_match {
+        // Plain -e only prints, while the shell function jumps through
+        // -e --learn, which counts as choosing the match
+        let learn = matches.get_flag("learn");
+        if matches.get_flag("echo") && !learn {
             println!("{}", best_match.path);
         } else if Path::new(&best_match.path).exists() {
+            remember_selection(&db, learned, pattern, &best_match.path);
             println!("{}", best_match.path);
-            db.add(&best_match.path);
+            if !learn {
+                db.add(&best_match.path);
+            }
         } else {
             db.remove(&best_match.path);
             eprintln!("z: directory no longer exists: {}", best_match.path);
             std::process::exit(1);
         }
-    } else if !pattern.is_empty() {
-        eprintln!("z: no matches found for: {}", pattern);
+    }
+}
+
+fn remember_selection(db: &ZDatabase, mut learned: Learned, pattern: &str, path: &str) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    learned.record(pattern, path, now);
+    if let Err(err) = db.save_learned(&learned) {
+        eprintln!("z: could not save learned selections: {}", err);
+    }
+}
+
+// Resolves the directory matches are restricted to, reporting why when the
+// scope has no root above the current directory.
+fn scope_dir(scope: Scope, matches: &ArgMatches) -> Option<String> {
+    let cwd = match env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(err) => {
+            eprintln!("z: cannot read current directory: {}", err);
+            return None;
+        }
+    };
+    let markers: Vec<String> = match matches.get_many::<String>("marker") {
+        Some(markers) => markers.cloned().collect(),
+        None => vcs::scope_markers(),
+    };
+    if scope == Scope::Marker && markers.is_empty() {
+        eprintln!("z: --scope marker needs --marker or _Z_SCOPE_MARKERS");
+        return None;
+    }
+
+    match scope.root(&cwd, &markers) {
+        Some(root) => Some(root.to_string_lossy().to_string()),
+        None => {
+            let what = match scope {
+                Scope::Marker => format!("directory containing {}", markers.join(" or ")),
+                _ => "repository root".to_string(),
+            };
+            eprintln!("z: no {} above {}", what, cwd.display());
+            None
+        }
+    }
+}
+
+fn jump_to_pin(db: &mut ZDatabase, alias: &str, pinned: &str, matches: &ArgMatches) {
+    if matches.get_flag("explain") {
+        println!("pinned: {} -> {}", alias, pinned);
+        println!("winner: {}", pinned);
+        println!("   decided by: pinned alias \"{}\"", alias);
+    } else if matches.get_flag("echo") {
+        println!("{}", pinned);
+    } else if Path::new(pinned).is_dir() {
+        println!("{}", pinned);
+        db.add(pinned);
+    } else {
+        eprintln!("z: pinned directory no longer exists: {}", pinned);
+        eprintln!("z: run `zjyo unpin {}` to remove the alias", alias);
         std::process::exit(1);
     }
 }
+
+// Marks listed directories that have pinned aliases or tags, as in
+// "  [pin: prod] [@acme @api]".
+fn list_labels(db: &ZDatabase, pins: &Pins, path: &str) -> String {
+    let mut labels = String::new();
+    let aliases = pins.aliases_for(path);
+    if !aliases.is_empty() {
+        labels.push_str(&format!(" [pin: {}]", aliases.join(", ")));
+    }
+    match db.entries.get(path) {
+        Some(entry) if !entry.tags.is_empty() => {
+            labels.push_str(&format!(" [{}]", tag_labels(&entry.tags)));
+        }
+        _ => {}
+    }
+    if labels.is_empty() {
+        labels
+    } else {
+        format!(" {}", labels)
+    }
+}
+
+fn run_merge(db: &mut ZDatabase, matches: &ArgMatches) {
+    let mut policy = match matches
+        .get_one::<String>("policy")
+        .unwrap()
+        .parse::<MergePolicy>()
+    {
+        Ok(policy) => policy,
+        Err(err) => {
+            eprintln!("z: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(weight) = matches.get_one::<f64>("weight") {
+        match policy {
+            MergePolicy::Weighted(_) => policy = MergePolicy::Weighted(*weight),
+            _ => {
+                eprintln!("z: --weight needs --policy weighted");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut incoming = Vec::new();
+    for file in matches.get_many::<String>("files").unwrap() {
+        match ZDatabase::read_entries(Path::new(file)) {
+            Ok(entries) => incoming.extend(entries),
+            Err(err) => {
+                eprintln!("z: cannot read {}: {}", file, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let dry_run = matches.get_flag("dry-run");
+    let report = db.merge(incoming, policy);
+    print_merge_report(&report, dry_run);
+    if report.is_empty() {
+        println!("z: nothing to merge");
+    }
+    if !dry_run {
+        db.snapshot();
+        db.save();
+    }
+}
+
+fn print_merge_report(report: &MergeReport, dry_run: bool) {
+    let prefix = if dry_run { "would " } else { "" };
+    for entry in &report.added {
+        println!("{}add     {:<10} {}", prefix, entry.rank, entry.path);
+    }
+    for (old, new) in &report.updated {
+        println!(
+            "{}update  {:<10} {} (was {})",
+            prefix, new.rank, new.path, old.rank
+        );
+    }
+    for entry in &report.aged_out {
+        println!("{}age out {:<10} {}", prefix, entry.rank, entry.path);
+    }
+}
+
+fn run_import(db: &mut ZDatabase, matches: &ArgMatches) {
+    let format = matches.get_one::<String>("format").unwrap();
+    let file = match matches.get_one::<String>("file") {
+        Some(file) => PathBuf::from(file),
+        None => match format.as_str() {
+            "zoxide" => import::default_zoxide_db().unwrap_or_default(),
+            "autojump" => import::default_autojump_data().unwrap_or_default(),
+            "fasd" => import::default_fasd_data().unwrap_or_default(),
+            _ => unreachable!(),
+        },
+    };
+
+    let bytes = if file.as_os_str() == "-" {
+        let mut buffer = Vec::new();
+        io::stdin().read_to_end(&mut buffer).map(|_| buffer)
+    } else {
+        fs::read(&file)
+    };
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("z: cannot read {}: {}", file.display(), err);
+            std::process::exit(1);
+        }
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    // autojump keeps no access times; its datafile's mtime is the best guess
+    let modified = fs::metadata(&file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(now, |time| time.as_secs());
+    let text = String::from_utf8_lossy(&bytes);
+    let parsed = match format.as_str() {
+        "zoxide" if import::is_zoxide_db(&bytes) => {
+            import::parse_zoxide_db(&bytes).map(|entries| (entries, Vec::new()))
+        }
+        "zoxide" => Ok(import::parse_zoxide_query(&text, now)),
+        "autojump" => Ok(import::parse_autojump(&text, modified)),
+        "fasd" => Ok(import::parse_fasd(&text)),
+        _ => unreachable!(),
+    };
+    let (mut entries, mut skipped) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("z: cannot import {}: {}", file.display(), err);
+            std::process::exit(1);
+        }
+    };
+    if format != "zoxide" {
+        let (directories, others) = import::partition_directories(entries);
+        entries = directories;
+        skipped.extend(others);
+    }
+
+    let mode = if matches.get_flag("replace") {
+        ImportMode::Replace
+    } else {
+        ImportMode::Merge
+    };
+    let dry_run = matches.get_flag("dry-run");
+    let mut report = db.import(entries, mode);
+    report.skipped = skipped;
+    print_import_report(&report, dry_run);
+    if !dry_run {
+        db.snapshot();
+        db.save();
+    }
+}
+
+fn print_import_report(report: &ImportReport, dry_run: bool) {
+    let prefix = if dry_run { "would " } else { "" };
+    for entry in &report.replaced {
+        println!("{}drop    {:<10} {}", prefix, entry.rank, entry.path);
+    }
+    for line in &report.skipped {
+        println!("skip    {}", line);
+    }
+    print_merge_report(&report.merge, dry_run);
+    println!(
+        "z: {} imported, {} merged, {} skipped",
+        report.merge.added.len(),
+        report.merge.updated.len(),
+        report.skipped.len()
+    );
+}
+
+fn run_export(db: &ZDatabase, matches: &ArgMatches) {
+    let format: ExportFormat = matches
+        .get_one::<String>("format")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let result = match matches.get_one::<String>("output") {
+        Some(file) => fs::File::create(file).and_then(|mut out| {
+            export::export(db.entries.values(), format, now, &mut out)?;
+            out.flush()
+        }),
+        None => {
+            let mut out = io::stdout().lock();
+            export::export(db.entries.values(), format, now, &mut out)
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("z: export failed: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run_undo(db: &mut ZDatabase) {
+    match db.undo() {
+        Ok(true) => println!("z: restored {} entries", db.entries.len()),
+        Ok(false) => {
+            eprintln!("z: no backups to restore");
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("z: undo failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_backups(db: &ZDatabase) {
+    let now = SystemTime::now();
+    for backup in db.backups() {
+        let age = backup
+            .modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map_or_else(|| "unknown".to_string(), |age| format_age(age.as_secs()));
+        println!(
+            "{:<3} {:<10} {:<10} {}",
+            backup.index,
+            age,
+            format!("{} entries", backup.entries),
+            backup.path.display()
+        );
+    }
+}
+
+// Summarizes the database, using the visit history where it is tracked and
+// falling back to rank for entries without one.
+fn run_stats(db: &ZDatabase, count: usize) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let tracked: Vec<_> = db
+        .entries
+        .values()
+        .filter_map(|entry| entry.history.as_ref().map(|history| (entry, history)))
+        .collect();
+    let total_rank: f64 = db.entries.values().map(|entry| entry.rank).sum();
+    let total_visits: u64 = tracked.iter().map(|(_, history)| history.visits).sum();
+
+    println!("entries:       {}", db.entries.len());
+    println!("total rank:    {:.2}", total_rank);
+    println!(
+        "visits:        {} across {} entries with history",
+        total_visits,
+        tracked.len()
+    );
+    if let Some((entry, first_seen)) = tracked
+        .iter()
+        .filter_map(|(entry, history)| history.first_seen.map(|time| (entry, time)))
+        .min_by_key(|(_, first_seen)| *first_seen)
+    {
+        println!(
+            "first visit:   {} ({})",
+            format_age(now.saturating_sub(first_seen)),
+            entry.path
+        );
+    }
+
+    let mut entries: Vec<_> = db.entries.values().collect();
+    entries.sort_by(|a, b| {
+        let visits = |entry: &DirEntry| entry.history.as_ref().map_or(0, |h| h.visits);
+        visits(b)
+            .cmp(&visits(a))
+            .then_with(|| b.rank.total_cmp(&a.rank))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    println!("most visited:");
+    println!(
+        "  {:<8} {:<10} {:<10} {:<10} path",
+        "visits", "first seen", "last visit", "rank"
+    );
+    for entry in entries.into_iter().take(count) {
+        let (visits, first_seen) = match &entry.history {
+            Some(history) => (
+                history.visits.to_string(),
+                history.first_seen.map_or("unknown".to_string(), |time| {
+                    format_age(now.saturating_sub(time))
+                }),
+            ),
+            None => ("-".to_string(), "unknown".to_string()),
+        };
+        println!(
+            "  {:<8} {:<10} {:<10} {:<10.2} {}",
+            visits,
+            first_seen,
+            format_age(now.saturating_sub(entry.time)),
+            entry.rank,
+            entry.path
+        );
+    }
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+fn run_pin(db: &ZDatabase, matches: &ArgMatches) {
+    let mut pins = db.load_pins();
+    let alias = match matches.get_one::<String>("alias") {
+        Some(alias) => alias,
+        None => {
+            for (alias, path) in &pins.aliases {
+                println!("{:<10} {}", alias, path);
+            }
+            return;
+        }
+    };
+
+    let path = directory_arg(matches.get_one::<String>("path"));
+    match pins.pin(alias, &path) {
+        Ok(previous) => {
+            if let Err(err) = db.save_pins(&pins) {
+                eprintln!("z: could not save pins: {}", err);
+                std::process::exit(1);
+            }
+            match previous {
+                Some(previous) if previous != path => {
+                    println!("z: pinned {} to {} (was {})", alias, path, previous)
+                }
+                _ => println!("z: pinned {} to {}", alias, path),
+            }
+        }
+        Err(err) => {
+            eprintln!("z: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_unpin(db: &ZDatabase, matches: &ArgMatches) {
+    let mut pins = db.load_pins();
+    let alias = matches.get_one::<String>("alias").unwrap();
+    match pins.unpin(alias) {
+        Some(path) => {
+            if let Err(err) = db.save_pins(&pins) {
+                eprintln!("z: could not save pins: {}", err);
+                std::process::exit(1);
+            }
+            println!("z: unpinned {} from {}", alias, path);
+        }
+        None => {
+            eprintln!("z: no pinned alias: {}", alias);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The absolute directory named on the command line, or the current one.
+fn directory_arg(path: Option<&String>) -> String {
+    let path = match path {
+        Some(path) => env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| PathBuf::from(path)),
+        None => env::current_dir().unwrap_or_default(),
+    };
+    match fs::canonicalize(&path) {
+        Ok(path) if path.is_dir() => path.to_string_lossy().to_string(),
+        _ => {
+            eprintln!("z: not a directory: {}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_tags(matches: &ArgMatches) -> Vec<String> {
+    let tags: Result<Vec<String>, String> = matches
+        .get_many::<String>("tags")
+        .unwrap()
+        .map(|tag| tags::parse_tag(tag))
+        .collect();
+    tags.unwrap_or_else(|err| {
+        eprintln!("z: {}", err);
+        std::process::exit(1);
+    })
+}
+
+// Tags a directory, adding it to the database first if it isn't tracked.
+fn run_tag(db: &mut ZDatabase, matches: &ArgMatches) {
+    let path = directory_arg(matches.get_one::<String>("path"));
+    let tags = parse_tags(matches);
+    db.track(&path);
+    if !db.tag(&path, &tags) {
+        eprintln!("z: cannot tag untracked directory: {}", path);
+        std::process::exit(1);
+    }
+    db.save();
+    let tagged = db.entries.get(&path).map_or(&tags, |entry| &entry.tags);
+    println!("z: {} tagged {}", path, tag_labels(tagged));
+}
+
+fn run_untag(db: &mut ZDatabase, matches: &ArgMatches) {
+    let path = directory_arg(matches.get_one::<String>("path"));
+    let tags = parse_tags(matches);
+    let removed = db.untag(&path, &tags);
+    if removed.is_empty() {
+        eprintln!("z: {} has none of the tags {}", path, tag_labels(&tags));
+        std::process::exit(1);
+    }
+    db.save();
+    println!("z: removed {} from {}", tag_labels(&removed), path);
+}
+
+// Lists every tag with the number of directories carrying it, or the
+// directories carrying the given tag.
+fn run_tags(db: &ZDatabase, matches: &ArgMatches) {
+    if let Some(tag) = matches.get_one::<String>("tag") {
+        let tag = tag.strip_prefix('@').unwrap_or(tag);
+        let mut paths: Vec<&str> = db
+            .entries
+            .values()
+            .filter(|entry| entry.tags.iter().any(|t| t == tag))
+            .map(|entry| entry.path.as_str())
+            .collect();
+        paths.sort();
+        for path in paths {
+            println!("{}", path);
+        }
+        return;
+    }
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in db.entries.values().flat_map(|entry| &entry.tags) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+    for (tag, count) in counts {
+        println!("@{:<15} {}", tag, count);
+    }
+}
+
+fn tag_labels(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("@{}", tag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn run_weight(db: &mut ZDatabase, matches: &ArgMatches) {
+    let factor = match matches.get_one::<String>("factor") {
+        Some(factor) => factor,
+        None => {
+            let mut entries: Vec<&DirEntry> = db
+                .entries
+                .values()
+                .filter(|entry| entry.weight != 1.0)
+                .collect();
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+            for entry in entries {
+                println!("{:<10} {}", entry.weight, entry.path);
+            }
+            return;
+        }
+    };
+    let weight = weight::parse_weight(factor).unwrap_or_else(|err| {
+        eprintln!("z: {}", err);
+        std::process::exit(1);
+    });
+
+    let path = directory_arg(matches.get_one::<String>("path"));
+    db.track(&path);
+    if !db.set_weight(&path, weight) {
+        eprintln!("z: cannot weight untracked directory: {}", path);
+        std::process::exit(1);
+    }
+    db.save();
+    if weight == 1.0 {
+        println!("z: reset the weight of {}", path);
+    } else {
+        println!("z: weighted {} by {}", path, weight);
+    }
+}
+
+fn run_learned(db: &ZDatabase, matches: &ArgMatches) {
+    if matches.get_flag("clear") {
+        match db.save_learned(&Learned::default()) {
+            Ok(()) => println!("z: forgot all learned selections"),
+            Err(err) => {
+                eprintln!("z: could not clear learned selections: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut selections = db.load_learned().selections;
+    selections.sort_by(|a, b| a.query.cmp(&b.query).then(b.count.cmp(&a.count)));
+    for selection in selections {
+        println!(
+            "{:<15} {:<6} {}",
+            selection.query, selection.count, selection.path
+        );
+    }
+}
+
+fn print_explanation(result: &SearchResult, scorer: &dyn Scorer) {
+    for (i, candidate) in result.candidates.iter().enumerate() {
+        let entry = &candidate.entry;
+        let marker = if i == 0 { "  [winner]" } else { "" };
+        println!("{}. {}{}", i + 1, entry.path, marker);
+        println!(
+            "   rank {}, last visit {}",
+            entry.rank,
+            format_age(result.now.saturating_sub(entry.time))
+        );
+        println!(
+            "   {}: {}",
+            scorer.name(),
+            scorer.explain(entry, result.now)
+        );
+        if !candidate.matched.is_empty() {
+            println!("   matched: {}", candidate.matched.join(", "));
+        }
+        for adjustment in &candidate.adjustments {
+            let kind = if adjustment.factor >= 1.0 {
+                "boost"
+            } else {
+                "penalty"
+            };
+            println!(
+                "   {} x{:.2}: {}",
+                kind, adjustment.factor, adjustment.reason
+            );
+        }
+        println!("   score: {:.2}", candidate.score);
+    }
+
+    for exclusion in &result.excluded {
+        println!("excluded: {} ({})", exclusion.entry.path, exclusion.reason);
+    }
+
+    match (result.candidates.first(), result.decided_by(scorer)) {
+        (Some(winner), Some(rule)) => {
+            println!("winner: {}", winner.entry.path);
+            println!("   decided by: {}", rule);
+        }
+        _ => println!("winner: none"),
+    }
+}
@@ -1,14 +1,20 @@
+use crate::aging::{Aging, Clock, SystemClock};
 use crate::entry::DirEntry;
+use crate::history::VisitHistory;
+use crate::query::Query;
+use crate::scorer::{Frecency, Rank, Recent, Scorer};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::env;
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 
 pub struct ZDatabase {
     pub entries: HashMap<String, DirEntry>,
     pub data_file: PathBuf,
+    // When the ranks were last decayed with a half-life, if ever
+    pub(crate) decayed_at: Option<u64>,
 }
 
 impl Default for ZDatabase {
@@ -23,32 +29,48 @@ impl ZDatabase {
             .map(PathBuf::from)
             .unwrap_or_else(|_| home::home_dir().unwrap().join(".z"));
 
-        let mut db = Self {
-            entries: HashMap::new(),
-            data_file,
-        };
+        let mut db = Self::with_data_file(data_file);
         db.load();
         db
     }
 
+    // An empty database backed by `data_file`, without loading it.
+    pub fn with_data_file(data_file: impl Into<PathBuf>) -> Self {
+        Self {
+            entries: HashMap::new(),
+            data_file: data_file.into(),
+            decayed_at: None,
+        }
+    }
+
     pub fn load(&mut self) {
         if !self.data_file.exists() {
             return;
         }
 
-        if let Ok(file) = File::open(&self.data_file) {
-            let reader = BufReader::new(file);
-            for line in reader.lines().map_while(Result::ok) {
-                let parts: Vec<&str> = line.splitn(3, '|').collect();
-                if parts.len() == 3 {
-                    if let (Ok(rank), Ok(time)) = (parts[1].parse::<f64>(), parts[2].parse::<u64>())
-                    {
-                        let entry = DirEntry::new(parts[0].to_string(), rank, time);
-                        self.entries.insert(parts[0].to_string(), entry);
-                    }
+        if let Ok(entries) = Self::read_entries(&self.data_file) {
+            for entry in entries {
+                self.entries.insert(entry.path.clone(), entry);
+            }
+        }
+        self.load_visits();
+        self.load_tags();
+        self.load_weights();
+        self.load_decay();
+    }
+
+    pub fn read_entries(path: &Path) -> io::Result<Vec<DirEntry>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+        for line in reader.lines().map_while(Result::ok) {
+            let parts: Vec<&str> = line.splitn(3, '|').collect();
+            if parts.len() == 3 {
+                if let (Ok(rank), Ok(time)) = (parts[1].parse::<f64>(), parts[2].parse::<u64>()) {
+                    entries.push(DirEntry::new(parts[0].to_string(), rank, time));
                 }
             }
         }
+        Ok(entries)
     }
 
     pub fn save(&self) {
@@ -62,109 +84,166 @@ impl ZDatabase {
                 writeln!(file, "{}|{}|{}", entry.path, entry.rank, entry.time).ok();
             }
         }
+        self.save_visits().ok();
+        self.save_tags().ok();
+        self.save_weights().ok();
+        self.save_decay().ok();
     }
 
     pub fn add(&mut self, path: &str) {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        if let Some(entry) = self.entries.get_mut(path) {
-            entry.rank += 1.0;
-            entry.time = now;
-        } else {
-            self.entries
-                .insert(path.to_string(), DirEntry::new(path.to_string(), 1.0, now));
+        self.add_with_increment(path, 1.0);
+    }
+
+    // Starts tracking a directory with a rank of 1 without counting a visit
+    // or aging the database, so it can be tagged or weighted right away.
+    pub fn track(&mut self, path: &str) {
+        let now = SystemClock.now();
+        self.entries
+            .entry(path.to_string())
+            .or_insert_with(|| DirEntry::new(path.to_string(), 1.0, now));
+    }
+
+    // Records a visit that raises the rank by `increment` instead of one.
+    pub fn add_with_increment(&mut self, path: &str, increment: f64) {
+        self.add_at(path, increment, Aging::from_env(), &SystemClock);
+    }
+
+    // Records a visit at the time given by `clock`, aging the database with
+    // `aging`.
+    pub fn add_at(&mut self, path: &str, increment: f64, aging: Aging, clock: &dyn Clock) {
+        self.visit_at(path, increment, clock.now(), aging, clock);
+    }
+
+    // Records a visit that started at `time`, which may be earlier than the
+    // clock when it is reported on leaving the directory.
+    pub fn visit_at(
+        &mut self,
+        path: &str,
+        increment: f64,
+        time: u64,
+        aging: Aging,
+        clock: &dyn Clock,
+    ) {
+        let aged_out =
+            self.change_and_age(aging, clock, |db| db.record_visit(path, increment, time));
+        if !aged_out.is_empty() {
+            self.snapshot();
         }
+        self.save();
+    }
 
+    // Applies `change` and ages the ranks around it: decay covers the time
+    // before the change, while z.sh ages the ranks after counting it.
+    // Returns the entries dropped.
+    pub(crate) fn change_and_age<F>(
+        &mut self,
+        aging: Aging,
+        clock: &dyn Clock,
+        change: F,
+    ) -> Vec<DirEntry>
+    where
+        F: FnOnce(&mut Self),
+    {
+        let decayed = match aging {
+            Aging::HalfLife(_) => self.age_with(aging, clock),
+            Aging::Threshold => Vec::new(),
+        };
+        change(self);
+        match aging {
+            Aging::HalfLife(_) => decayed,
+            Aging::Threshold => self.age_with(aging, clock),
+        }
+    }
+
+    fn record_visit(&mut self, path: &str, increment: f64, time: u64) {
+        let entry = match self.entries.entry(path.to_string()) {
+            Entry::Occupied(occupied) => {
+                let entry = occupied.into_mut();
+                // Entries imported or tracked before histories were kept
+                // start from what the datafile knows about them
+                let (rank, time) = (entry.rank, entry.time);
+                entry
+                    .history
+                    .get_or_insert_with(|| VisitHistory::seeded(rank, time))
+                    .record(time);
+                entry.rank += increment;
+                entry
+            }
+            Entry::Vacant(vacant) => {
+                let mut entry = DirEntry::new(path.to_string(), increment, time);
+                entry.history = Some(VisitHistory::new(time));
+                vacant.insert(entry)
+            }
+        };
+        // A visit reported late must not move the last access backwards
+        entry.time = entry.time.max(time);
+        entry.visits.record(time);
+    }
+
+    pub fn age(&mut self) -> Vec<DirEntry> {
+        self.age_with(Aging::from_env(), &SystemClock)
+    }
+
+    // Ages the ranks with the given strategy, returning the entries dropped.
+    pub fn age_with(&mut self, aging: Aging, clock: &dyn Clock) -> Vec<DirEntry> {
+        match aging {
+            Aging::Threshold => self.age_threshold(),
+            Aging::HalfLife(half_life) => self.decay(half_life, clock.now()),
+        }
+    }
+
+    fn age_threshold(&mut self) -> Vec<DirEntry> {
+        let mut aged_out = Vec::new();
         let total_rank: f64 = self.entries.values().map(|e| e.rank).sum();
         if total_rank > 9000.0 {
+            // Weighted entries decay like any other but are never dropped,
+            // so neither a boost nor a demotion is lost when they fade
             self.entries.retain(|_, entry| {
                 entry.rank *= 0.99;
-                entry.rank >= 1.0
+                let keep = entry.rank >= 1.0 || entry.weight != 1.0;
+                if !keep {
+                    aged_out.push(entry.clone());
+                }
+                keep
             });
         }
-
-        self.save();
+        aged_out.sort_by(|a, b| a.path.cmp(&b.path));
+        aged_out
     }
 
     pub fn remove(&mut self, path: &str) {
-        self.entries.remove(path);
+        if self.entries.remove(path).is_some() {
+            self.snapshot();
+        }
         self.save();
     }
 
     pub fn find_matches(&self, pattern: &str, current_dir: Option<&str>) -> Vec<DirEntry> {
-        let mut matches: Vec<DirEntry> = self
-            .entries
-            .values()
-            .filter(|entry| {
-                let path_lower = entry.path.to_lowercase();
-
-                // Split pattern by whitespace and check that ALL words are contained in the path
-                let pattern_words: Vec<&str> = pattern.split_whitespace().collect();
-                let path_contains_all_words = if pattern_words.is_empty() {
-                    true
-                } else {
-                    pattern_words
-                        .iter()
-                        .all(|word| path_lower.contains(&word.to_lowercase()))
-                };
-
-                if let Some(current) = current_dir {
-                    path_lower.starts_with(&current.to_lowercase()) && path_contains_all_words
-                } else {
-                    path_contains_all_words
-                }
-            })
-            .cloned()
-            .collect();
-
-        matches.sort_by_key(|b| std::cmp::Reverse(b.frecency()));
-        matches
+        self.find_with(pattern, current_dir, &Frecency)
     }
 
     pub fn find_by_rank(&self, pattern: &str) -> Vec<DirEntry> {
-        let mut matches: Vec<DirEntry> = self
-            .entries
-            .values()
-            .filter(|entry| {
-                let path_lower = entry.path.to_lowercase();
-                let pattern_words: Vec<&str> = pattern.split_whitespace().collect();
-                if pattern_words.is_empty() {
-                    true
-                } else {
-                    pattern_words
-                        .iter()
-                        .all(|word| path_lower.contains(&word.to_lowercase()))
-                }
-            })
-            .cloned()
-            .collect();
-
-        matches.sort_by(|a, b| b.rank.partial_cmp(&a.rank).unwrap());
-        matches
+        self.find_with(pattern, None, &Rank)
     }
 
     pub fn find_by_time(&self, pattern: &str) -> Vec<DirEntry> {
-        let mut matches: Vec<DirEntry> = self
-            .entries
-            .values()
-            .filter(|entry| {
-                let path_lower = entry.path.to_lowercase();
-                let pattern_words: Vec<&str> = pattern.split_whitespace().collect();
-                if pattern_words.is_empty() {
-                    true
-                } else {
-                    pattern_words
-                        .iter()
-                        .all(|word| path_lower.contains(&word.to_lowercase()))
-                }
-            })
-            .cloned()
-            .collect();
+        self.find_with(pattern, None, &Recent)
+    }
 
-        matches.sort_by(|a, b| b.time.cmp(&a.time));
-        matches
+    pub fn find_with(
+        &self,
+        pattern: &str,
+        current_dir: Option<&str>,
+        scorer: &dyn Scorer,
+    ) -> Vec<DirEntry> {
+        let query = Query {
+            current_dir,
+            ..Query::new(pattern, scorer)
+        };
+        self.search(&query)
+            .candidates
+            .into_iter()
+            .map(|candidate| candidate.entry)
+            .collect()
     }
 }
@@ -0,0 +1,59 @@
+use crate::aging::{Aging, Clock, SystemClock};
+use crate::database::ZDatabase;
+use std::env;
+
+// Time spent in a directory that counts as one visit.
+pub const DWELL_UNIT: u64 = 5 * 60;
+
+// Bounds of the rank increment for a single stay, so an afternoon in one
+// directory can't outweigh days of regular visits.
+pub const MIN_INCREMENT: f64 = 0.25;
+pub const MAX_INCREMENT: f64 = 4.0;
+
+// Stays shorter than this are ignored unless `_Z_MIN_DWELL` says otherwise.
+pub const DEFAULT_MIN_DWELL: u64 = 5;
+
+// The minimum stay in seconds configured through `_Z_MIN_DWELL`.
+pub fn min_dwell() -> u64 {
+    match env::var("_Z_MIN_DWELL") {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            eprintln!("z: invalid seconds {} in _Z_MIN_DWELL", value);
+            DEFAULT_MIN_DWELL
+        }),
+        Err(_) => DEFAULT_MIN_DWELL,
+    }
+}
+
+// The rank increment for a stay of `dwell` seconds: one per DWELL_UNIT,
+// capped at both ends. Stays below `min_dwell`, such as directories passed
+// through by a chain of cds, don't count at all.
+pub fn dwell_increment(dwell: u64, min_dwell: u64) -> Option<f64> {
+    if dwell < min_dwell {
+        return None;
+    }
+    Some((dwell as f64 / DWELL_UNIT as f64).clamp(MIN_INCREMENT, MAX_INCREMENT))
+}
+
+impl ZDatabase {
+    // Records leaving `path` after `dwell` seconds, returning the rank
+    // increment or None when the stay was too short to count.
+    pub fn leave(&mut self, path: &str, dwell: u64, min_dwell: u64) -> Option<f64> {
+        self.leave_at(path, dwell, min_dwell, Aging::from_env(), &SystemClock)
+    }
+
+    // Records leaving at the time given by `clock`. The visit is dated to
+    // the arrival, so histograms and history count the hour it started.
+    pub fn leave_at(
+        &mut self,
+        path: &str,
+        dwell: u64,
+        min_dwell: u64,
+        aging: Aging,
+        clock: &dyn Clock,
+    ) -> Option<f64> {
+        let increment = dwell_increment(dwell, min_dwell)?;
+        let arrived = clock.now().saturating_sub(dwell);
+        self.visit_at(path, increment, arrived, aging, clock);
+        Some(increment)
+    }
+}
@@ -1,3 +1,5 @@
+use crate::histogram::VisitHistogram;
+use crate::history::VisitHistory;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
@@ -5,19 +7,39 @@ pub struct DirEntry {
     pub path: String,
     pub rank: f64,
     pub time: u64,
+    // Kept in a sidecar next to the datafile
+    pub visits: VisitHistogram,
+    // First visit, true visit count and recent visits, when tracked
+    pub history: Option<VisitHistory>,
+    // Sorted tag names, without the leading `@`
+    pub tags: Vec<String>,
+    // Manual multiplier applied to every score; unlike rank it is never aged
+    pub weight: f64,
 }
 
 impl DirEntry {
     pub fn new(path: String, rank: f64, time: u64) -> Self {
-        Self { path, rank, time }
+        Self {
+            path,
+            rank,
+            time,
+            visits: VisitHistogram::default(),
+            history: None,
+            tags: Vec::new(),
+            weight: 1.0,
+        }
     }
 
-    pub fn frecency(&self) -> i32 {
+    pub fn frecency(&self) -> f64 {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let dx = now - self.time;
-        (10000.0 * self.rank * (3.75 / ((0.0001 * dx as f64 + 1.0) + 0.25))) as i32
+        self.frecency_at(now)
+    }
+
+    pub fn frecency_at(&self, now: u64) -> f64 {
+        let dx = now.saturating_sub(self.time);
+        10000.0 * self.rank * (3.75 / ((0.0001 * dx as f64 + 1.0) + 0.25))
     }
 }
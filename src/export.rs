@@ -0,0 +1,120 @@
+use crate::entry::DirEntry;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Tsv,
+    Zoxide,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "tsv" => Ok(ExportFormat::Tsv),
+            "zoxide" => Ok(ExportFormat::Zoxide),
+            _ => Err(format!("unknown export format: {}", s)),
+        }
+    }
+}
+
+pub fn export<'a, I, W>(entries: I, format: ExportFormat, now: u64, out: &mut W) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a DirEntry>,
+    W: Write,
+{
+    let mut entries: Vec<&DirEntry> = entries.into_iter().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    match format {
+        ExportFormat::Json => {
+            writeln!(out, "[")?;
+            for (i, entry) in entries.iter().enumerate() {
+                let separator = if i + 1 < entries.len() { "," } else { "" };
+                writeln!(
+                    out,
+                    "  {{\"path\": {}, \"rank\": {}, \"time\": {}, \"frecency\": {}}}{}",
+                    json_string(&entry.path),
+                    entry.rank,
+                    entry.time,
+                    entry.frecency_at(now),
+                    separator
+                )?;
+            }
+            writeln!(out, "]")?;
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "path,rank,time,frecency")?;
+            for entry in entries {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    csv_field(&entry.path),
+                    entry.rank,
+                    entry.time,
+                    entry.frecency_at(now)
+                )?;
+            }
+        }
+        ExportFormat::Tsv => {
+            writeln!(out, "path\trank\ttime\tfrecency")?;
+            for entry in entries {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}",
+                    tsv_field(&entry.path),
+                    entry.rank,
+                    entry.time,
+                    entry.frecency_at(now)
+                )?;
+            }
+        }
+        // `zoxide import --from=z` reads z's own path|rank|time datafile layout
+        ExportFormat::Zoxide => {
+            for entry in entries {
+                writeln!(out, "{}|{}|{}", entry.path, entry.rank, entry.time)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn tsv_field(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
@@ -0,0 +1,22 @@
+use std::env;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Accent-insensitive matching is enabled through `_Z_IGNORE_ACCENTS=1`.
+pub fn accents_ignored() -> bool {
+    env::var("_Z_IGNORE_ACCENTS").is_ok_and(|value| value == "1")
+}
+
+// Canonical caseless form of `s` used for all pattern matching: fully case
+// folded and recomposed (NFC), so "Straße" matches "STRASSE" and composed and
+// decomposed forms of "café" compare equal without "cafe" matching "café".
+// With `ignore_accents` the combining marks are dropped before recomposing,
+// so "café" also matches "cafe".
+pub fn fold(s: &str, ignore_accents: bool) -> String {
+    let decomposed: String = s.nfd().collect();
+    caseless::default_case_fold_str(&decomposed)
+        .nfd()
+        .filter(|c| !(ignore_accents && is_combining_mark(*c)))
+        .nfc()
+        .collect()
+}
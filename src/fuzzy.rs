@@ -0,0 +1,92 @@
+// Subsequence matching in the style of fzy: every character of the needle
+// must appear in order in the haystack, and alignments that land on word
+// boundaries or run consecutively score higher than scattered ones.
+
+use crate::fold::fold;
+use unicode_normalization::UnicodeNormalization;
+
+const SCORE_CONSECUTIVE: f64 = 1.0;
+const SCORE_AFTER_SLASH: f64 = 0.9;
+const SCORE_AFTER_SEPARATOR: f64 = 0.8;
+const SCORE_CAPITAL: f64 = 0.7;
+const SCORE_AFTER_DOT: f64 = 0.6;
+const SCORE_GAP: f64 = -0.01;
+const MIN_QUALITY: f64 = 0.05;
+
+// Returns the quality of the best alignment of `needle` in `haystack` in
+// (0, 1], where 1 means the needle appears consecutively starting at a path
+// component, or None if the needle is not a subsequence.
+pub fn quality(needle: &str, haystack: &str, ignore_accents: bool) -> Option<f64> {
+    let needle: Vec<char> = fold(needle, ignore_accents).chars().collect();
+    if needle.is_empty() {
+        return Some(1.0);
+    }
+
+    // Fold the haystack one character at a time so that every folded
+    // character keeps the boundary bonus of the character it came from
+    let original: Vec<char> = haystack.nfc().collect();
+    let mut lower: Vec<char> = Vec::with_capacity(original.len());
+    let mut bonus: Vec<f64> = Vec::with_capacity(original.len());
+    for (j, c) in original.iter().enumerate() {
+        for (k, folded) in fold(&c.to_string(), ignore_accents).chars().enumerate() {
+            lower.push(folded);
+            bonus.push(if k == 0 {
+                boundary_bonus(&original, j)
+            } else {
+                0.0
+            });
+        }
+    }
+    if needle.len() > lower.len() {
+        return None;
+    }
+
+    // best[j]: best score for the needle so far with its last char at j
+    let mut best: Vec<Option<f64>> = lower
+        .iter()
+        .enumerate()
+        .map(|(j, c)| (*c == needle[0]).then(|| bonus[j]))
+        .collect();
+
+    for &wanted in &needle[1..] {
+        let mut next = vec![None; lower.len()];
+        // Best of best[k] - gap * k for k < j - 1, to charge the gap to j
+        let mut best_before: Option<f64> = None;
+        for j in 1..lower.len() {
+            if j >= 2 {
+                if let Some(score) = best[j - 2] {
+                    let shifted = score - SCORE_GAP * (j - 2) as f64;
+                    best_before = Some(best_before.map_or(shifted, |b: f64| b.max(shifted)));
+                }
+            }
+            if lower[j] != wanted {
+                continue;
+            }
+            let consecutive = best[j - 1].map(|score| score + SCORE_CONSECUTIVE);
+            let gapped = best_before.map(|b| b + SCORE_GAP * (j - 1) as f64 + bonus[j]);
+            next[j] = match (consecutive, gapped) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        best = next;
+    }
+
+    let score = best.into_iter().flatten().reduce(f64::max)?;
+    let max = SCORE_AFTER_SLASH + SCORE_CONSECUTIVE * (needle.len() - 1) as f64;
+    Some((score / max).clamp(MIN_QUALITY, 1.0))
+}
+
+fn boundary_bonus(chars: &[char], j: usize) -> f64 {
+    if j == 0 {
+        return SCORE_AFTER_SLASH;
+    }
+    let (prev, current) = (chars[j - 1], chars[j]);
+    match prev {
+        '/' => SCORE_AFTER_SLASH,
+        '-' | '_' | ' ' => SCORE_AFTER_SEPARATOR,
+        '.' => SCORE_AFTER_DOT,
+        _ if prev.is_lowercase() && current.is_uppercase() => SCORE_CAPITAL,
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,146 @@
+use crate::database::ZDatabase;
+use crate::history::VisitHistory;
+use crate::sidecar;
+use chrono::{Datelike, Local, TimeZone, Timelike};
+use std::io;
+use std::path::PathBuf;
+
+// Once an entry has this many recorded visits all buckets are halved, so
+// the histogram keeps its shape but follows changes in habits.
+const MAX_VISITS: u32 = 1000;
+
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+// Visits per local hour of the day and per weekday, Monday first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisitHistogram {
+    pub hours: [u32; 24],
+    pub weekdays: [u32; 7],
+}
+
+impl VisitHistogram {
+    pub fn record(&mut self, time: u64) {
+        if self.total() >= MAX_VISITS {
+            self.hours.iter_mut().for_each(|count| *count /= 2);
+            self.weekdays.iter_mut().for_each(|count| *count /= 2);
+        }
+        let (hour, weekday) = local_slot(time);
+        self.hours[hour] += 1;
+        self.weekdays[weekday] += 1;
+    }
+
+    pub fn total(&self) -> u32 {
+        self.hours.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    // The shares of visits made around the hour of `time` and on its
+    // weekday, both between 0 and 1. Neighbouring hours count half.
+    pub fn similarity(&self, time: u64) -> (f64, f64) {
+        let total = self.total();
+        if total == 0 {
+            return (0.0, 0.0);
+        }
+        let (hour, weekday) = local_slot(time);
+        let around = self.hours[hour] as f64
+            + 0.5 * self.hours[(hour + 23) % 24] as f64
+            + 0.5 * self.hours[(hour + 1) % 24] as f64;
+        let total = total as f64;
+        (
+            (around / total).min(1.0),
+            self.weekdays[weekday] as f64 / total,
+        )
+    }
+
+    fn parse(hours: &str, weekdays: &str) -> Option<Self> {
+        let mut histogram = Self::default();
+        parse_counts(hours, &mut histogram.hours)?;
+        parse_counts(weekdays, &mut histogram.weekdays)?;
+        Some(histogram)
+    }
+}
+
+// The local hour (0-23) and weekday (0 for Monday) of a unix timestamp.
+pub fn local_slot(time: u64) -> (usize, usize) {
+    match Local.timestamp_opt(time as i64, 0).single() {
+        Some(local) => (
+            local.hour() as usize,
+            local.weekday().num_days_from_monday() as usize,
+        ),
+        None => (0, 0),
+    }
+}
+
+fn parse_counts(field: &str, counts: &mut [u32]) -> Option<()> {
+    let values: Vec<&str> = field.split(',').collect();
+    if values.len() != counts.len() {
+        return None;
+    }
+    for (count, value) in counts.iter_mut().zip(values) {
+        *count = value.parse().ok()?;
+    }
+    Some(())
+}
+
+fn join_counts(counts: &[u32]) -> String {
+    counts
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl ZDatabase {
+    // Histograms and visit histories share one sidecar, so a visit rewrites
+    // a single file next to the datafile.
+    pub fn visits_file(&self) -> PathBuf {
+        self.sidecar_file("hist")
+    }
+
+    // Attaches the histograms and histories in the sidecar to loaded
+    // entries. Lines are `path|hours|weekdays|first_seen|visits|recent`,
+    // with the history fields left empty for entries that have none. Lines
+    // for paths that are no longer in the datafile are ignored.
+    pub fn load_visits(&mut self) {
+        for line in sidecar::read_lines(&self.visits_file()) {
+            let parts: Vec<&str> = line.rsplitn(6, '|').collect();
+            if let [recent, visits, first_seen, weekdays, hours, path] = parts[..] {
+                if let (Some(entry), Some(histogram)) = (
+                    self.entries.get_mut(path),
+                    VisitHistogram::parse(hours, weekdays),
+                ) {
+                    entry.visits = histogram;
+                    entry.history = VisitHistory::parse(first_seen, visits, recent);
+                }
+            }
+        }
+    }
+
+    pub fn save_visits(&self) -> io::Result<()> {
+        let mut entries: Vec<_> = self
+            .entries
+            .values()
+            .filter(|entry| !entry.visits.is_empty() || entry.history.is_some())
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let lines: Vec<String> = entries
+            .into_iter()
+            .map(|entry| {
+                format!(
+                    "{}|{}|{}|{}",
+                    entry.path,
+                    join_counts(&entry.visits.hours),
+                    join_counts(&entry.visits.weekdays),
+                    entry
+                        .history
+                        .as_ref()
+                        .map_or("||".to_string(), VisitHistory::format)
+                )
+            })
+            .collect();
+        sidecar::write_lines(&self.visits_file(), &lines)
+    }
+}
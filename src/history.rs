@@ -0,0 +1,70 @@
+// Number of most recent visit timestamps kept per entry.
+pub const RECENT_VISITS: usize = 10;
+
+// Extended metadata that the z format has no room for. Unlike rank, the
+// visit count is never aged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisitHistory {
+    // None for entries tracked before their history was kept
+    pub first_seen: Option<u64>,
+    pub visits: u64,
+    // Oldest first, at most RECENT_VISITS long
+    pub recent: Vec<u64>,
+}
+
+impl VisitHistory {
+    pub fn new(time: u64) -> Self {
+        Self {
+            first_seen: Some(time),
+            visits: 1,
+            recent: vec![time],
+        }
+    }
+
+    // Starts the history of an entry that was tracked before histories were
+    // kept. Its rank is the best available visit count, even though aging
+    // may have lowered it, and its last access is the only visit time known.
+    pub fn seeded(rank: f64, last_visit: u64) -> Self {
+        Self {
+            first_seen: None,
+            visits: rank.round().max(1.0) as u64,
+            recent: vec![last_visit],
+        }
+    }
+
+    pub fn record(&mut self, time: u64) {
+        self.visits += 1;
+        self.recent.push(time);
+        if self.recent.len() > RECENT_VISITS {
+            self.recent.remove(0);
+        }
+    }
+
+    pub(crate) fn parse(first_seen: &str, visits: &str, recent: &str) -> Option<Self> {
+        let recent = if recent.is_empty() {
+            Vec::new()
+        } else {
+            recent
+                .split(',')
+                .map(|time| time.parse().ok())
+                .collect::<Option<Vec<u64>>>()?
+        };
+        Some(Self {
+            first_seen: match first_seen {
+                "" => None,
+                first_seen => Some(first_seen.parse().ok()?),
+            },
+            visits: visits.parse().ok()?,
+            recent,
+        })
+    }
+
+    // The first seen, visits and recent visits fields of a sidecar line.
+    pub(crate) fn format(&self) -> String {
+        let recent: Vec<String> = self.recent.iter().map(u64::to_string).collect();
+        let first_seen = self
+            .first_seen
+            .map_or(String::new(), |time| time.to_string());
+        format!("{}|{}|{}", first_seen, self.visits, recent.join(","))
+    }
+}
@@ -0,0 +1,210 @@
+use crate::database::ZDatabase;
+use crate::entry::DirEntry;
+use crate::merge::{MergePolicy, MergeReport};
+use std::env;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+const ZOXIDE_DB_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub merge: MergeReport,
+    pub replaced: Vec<DirEntry>,
+    pub skipped: Vec<String>,
+}
+
+impl ZDatabase {
+    pub fn import(&mut self, entries: Vec<DirEntry>, mode: ImportMode) -> ImportReport {
+        let previous = match mode {
+            ImportMode::Merge => Default::default(),
+            ImportMode::Replace => mem::take(&mut self.entries),
+        };
+
+        let merge = self.merge(entries, MergePolicy::Sum);
+
+        let mut replaced: Vec<DirEntry> = previous
+            .into_values()
+            .filter(|entry| !self.entries.contains_key(&entry.path))
+            .collect();
+        replaced.sort_by(|a, b| a.path.cmp(&b.path));
+
+        ImportReport {
+            merge,
+            replaced,
+            skipped: Vec::new(),
+        }
+    }
+}
+
+pub fn default_zoxide_db() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("_ZO_DATA_DIR") {
+        return Some(PathBuf::from(dir).join("db.zo"));
+    }
+    let data_dir = match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ if cfg!(target_os = "macos") => home::home_dir()?.join("Library/Application Support"),
+        _ => home::home_dir()?.join(".local/share"),
+    };
+    Some(data_dir.join("zoxide").join("db.zo"))
+}
+
+pub fn default_autojump_data() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        return Some(home::home_dir()?.join("Library/autojump/autojump.txt"));
+    }
+    let data_dir = match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home::home_dir()?.join(".local/share"),
+    };
+    Some(data_dir.join("autojump").join("autojump.txt"))
+}
+
+pub fn default_fasd_data() -> Option<PathBuf> {
+    match env::var("_FASD_DATA") {
+        Ok(file) => Some(PathBuf::from(file)),
+        Err(_) => Some(home::home_dir()?.join(".fasd")),
+    }
+}
+
+pub fn is_zoxide_db(bytes: &[u8]) -> bool {
+    bytes.len() >= 4 && bytes[..4] == ZOXIDE_DB_VERSION.to_le_bytes()
+}
+
+// zoxide stores its database with bincode: a u32 version followed by a
+// u64-length-prefixed list of (path, rank, last_accessed) records.
+pub fn parse_zoxide_db(bytes: &[u8]) -> io::Result<Vec<DirEntry>> {
+    let mut reader = ByteReader { bytes, pos: 0 };
+
+    let version = reader.u32()?;
+    if version != ZOXIDE_DB_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported zoxide database version: {}",
+            version
+        )));
+    }
+
+    let count = reader.u64()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let len = reader.u64()? as usize;
+        let path = String::from_utf8(reader.take(len)?.to_vec())
+            .map_err(|_| invalid_data("zoxide database contains a non-UTF-8 path".to_string()))?;
+        let rank = reader.f64()?;
+        let time = reader.u64()?;
+        entries.push(DirEntry::new(path, rank, time));
+    }
+    Ok(entries)
+}
+
+// `zoxide query -ls` prints "<score> <path>" without the access time. The
+// entries are treated as visited at `now`, where zoxide multiplies rank by 4.
+pub fn parse_zoxide_query(text: &str, now: u64) -> (Vec<DirEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_start();
+        if line.is_empty() {
+            continue;
+        }
+        match line.split_once(' ') {
+            Some((score, path)) if !path.trim().is_empty() => match score.parse::<f64>() {
+                Ok(score) => entries.push(DirEntry::new(path.trim().to_string(), score / 4.0, now)),
+                Err(_) => skipped.push(line.to_string()),
+            },
+            _ => skipped.push(line.to_string()),
+        }
+    }
+    (entries, skipped)
+}
+
+// autojump grows weights as sqrt(w^2 + 10^2) per visit, so (w / 10)^2
+// approximates the number of visits z would have counted. autojump keeps no
+// access times; entries are treated as last visited at `time`.
+pub fn parse_autojump(text: &str, time: u64) -> (Vec<DirEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match line.split_once('\t') {
+            Some((weight, path)) if !path.is_empty() => match weight.trim().parse::<f64>() {
+                Ok(weight) => {
+                    let rank = ((weight / 10.0).powi(2)).max(1.0);
+                    entries.push(DirEntry::new(path.to_string(), rank, time));
+                }
+                Err(_) => skipped.push(line.to_string()),
+            },
+            _ => skipped.push(line.to_string()),
+        }
+    }
+    (entries, skipped)
+}
+
+// fasd shares z's path|rank|time layout but grows ranks as r + 1/r per
+// visit, so r^2 / 2 approximates the visit count.
+pub fn parse_fasd(text: &str) -> (Vec<DirEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let parts: Vec<&str> = line.rsplitn(3, '|').collect();
+        if parts.len() == 3 {
+            if let (Ok(rank), Ok(time)) = (parts[1].parse::<f64>(), parts[0].parse::<u64>()) {
+                let rank = (rank * rank / 2.0).max(1.0);
+                entries.push(DirEntry::new(parts[2].to_string(), rank, time));
+                continue;
+            }
+        }
+        skipped.push(line.to_string());
+    }
+    (entries, skipped)
+}
+
+pub fn partition_directories(entries: Vec<DirEntry>) -> (Vec<DirEntry>, Vec<String>) {
+    let (directories, others): (Vec<DirEntry>, Vec<DirEntry>) = entries
+        .into_iter()
+        .partition(|entry| Path::new(&entry.path).is_dir());
+    (
+        directories,
+        others.into_iter().map(|entry| entry.path).collect(),
+    )
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid_data("truncated zoxide database".to_string()))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
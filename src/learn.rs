@@ -0,0 +1,158 @@
+use crate::database::ZDatabase;
+use crate::fold::fold;
+use crate::sidecar;
+use std::cmp::Reverse;
+use std::io;
+use std::path::PathBuf;
+
+// At most this many (query, path) pairs are kept; the least recently chosen
+// ones are forgotten first.
+pub const MAX_SELECTIONS: usize = 500;
+
+// A directory that was jumped to for a query, and how often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub query: String,
+    pub path: String,
+    pub count: u32,
+    pub time: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Learned {
+    pub selections: Vec<Selection>,
+}
+
+impl Learned {
+    // Queries are compared case folded with whitespace collapsed.
+    pub fn normalize(query: &str) -> String {
+        fold(query, false)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn record(&mut self, query: &str, path: &str, now: u64) {
+        // The separator would make the stored line ambiguous
+        let query = Self::normalize(query);
+        if query.is_empty() || query.contains('|') {
+            return;
+        }
+        match self
+            .selections
+            .iter_mut()
+            .find(|selection| selection.query == query && selection.path == path)
+        {
+            Some(selection) => {
+                selection.count += 1;
+                selection.time = now;
+            }
+            None => self.selections.push(Selection {
+                query,
+                path: path.to_string(),
+                count: 1,
+                time: now,
+            }),
+        }
+
+        if self.selections.len() > MAX_SELECTIONS {
+            self.selections
+                .sort_by_key(|selection| Reverse(selection.time));
+            self.selections.truncate(MAX_SELECTIONS);
+        }
+    }
+
+    // How often `path` was chosen for exactly `query`, and for longer
+    // queries that start with it.
+    pub fn selections_for(&self, query: &str, path: &str) -> (u32, u32) {
+        let query = Self::normalize(query);
+        if query.is_empty() {
+            return (0, 0);
+        }
+        self.selections
+            .iter()
+            .filter(|selection| selection.path == path)
+            .fold((0, 0), |(exact, prefix), selection| {
+                if selection.query == query {
+                    (exact + selection.count, prefix)
+                } else if selection.query.starts_with(&query) {
+                    (exact, prefix + selection.count)
+                } else {
+                    (exact, prefix)
+                }
+            })
+    }
+
+    // Score multiplier for a path chosen before: exact selections count
+    // fully and prefix ones half, on a logarithmic scale so a handful of
+    // jumps matter but hundreds don't drown out frecency.
+    pub fn boost(&self, query: &str, path: &str) -> Option<(f64, String)> {
+        let (exact, prefix) = self.selections_for(query, path);
+        if exact == 0 && prefix == 0 {
+            return None;
+        }
+        let strength = exact as f64 + 0.5 * prefix as f64;
+        let query = Self::normalize(query);
+        let reason = match (exact, prefix) {
+            (_, 0) => format!("chosen {} for \"{}\"", times(exact), query),
+            (0, _) => format!(
+                "chosen {} for queries starting with \"{}\"",
+                times(prefix),
+                query
+            ),
+            _ => format!(
+                "chosen {} for \"{}\" and {} for longer queries",
+                times(exact),
+                query,
+                times(prefix)
+            ),
+        };
+        Some((1.0 + strength.ln_1p(), reason))
+    }
+}
+
+fn times(count: u32) -> String {
+    match count {
+        1 => "once".to_string(),
+        _ => format!("{} times", count),
+    }
+}
+
+impl ZDatabase {
+    pub fn learned_file(&self) -> PathBuf {
+        self.sidecar_file("learned")
+    }
+
+    pub fn load_learned(&self) -> Learned {
+        let mut learned = Learned::default();
+        for line in sidecar::read_lines(&self.learned_file()) {
+            let parts: Vec<&str> = line.splitn(4, '|').collect();
+            if let [count, time, query, path] = parts[..] {
+                if let (Ok(count), Ok(time)) = (count.parse(), time.parse()) {
+                    learned.selections.push(Selection {
+                        query: query.to_string(),
+                        path: path.to_string(),
+                        count,
+                        time,
+                    });
+                }
+            }
+        }
+        learned
+    }
+
+    // Queries never contain `|`, so they go before the path, which may.
+    pub fn save_learned(&self, learned: &Learned) -> io::Result<()> {
+        let lines: Vec<String> = learned
+            .selections
+            .iter()
+            .map(|selection| {
+                format!(
+                    "{}|{}|{}|{}",
+                    selection.count, selection.time, selection.query, selection.path
+                )
+            })
+            .collect();
+        sidecar::write_lines(&self.learned_file(), &lines)
+    }
+}
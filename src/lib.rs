@@ -1,10 +1,42 @@
+pub mod aging;
+pub mod backup;
 pub mod cli;
 pub mod database;
+pub mod dwell;
 pub mod entry;
+pub mod export;
+pub mod fold;
+pub mod fuzzy;
+pub mod histogram;
+pub mod history;
+pub mod import;
+pub mod learn;
+pub mod matcher;
+pub mod merge;
+pub mod pins;
+pub mod query;
+pub mod scorer;
+pub mod sidecar;
+pub mod suggest;
+pub mod tags;
+pub mod vcs;
+pub mod weight;
 
 #[cfg(test)]
 mod tests;
 
+pub use aging::{Aging, Clock, FixedClock, SystemClock};
+pub use backup::Backup;
 pub use cli::run;
 pub use database::ZDatabase;
 pub use entry::DirEntry;
+pub use export::ExportFormat;
+pub use histogram::VisitHistogram;
+pub use history::VisitHistory;
+pub use import::{ImportMode, ImportReport};
+pub use matcher::MatchMode;
+pub use merge::{MergePolicy, MergeReport};
+pub use pins::Pins;
+pub use query::{Candidate, Query, SearchResult};
+pub use scorer::Scorer;
+pub use suggest::Suggestion;
@@ -0,0 +1,260 @@
+use crate::fold::fold;
+use crate::fuzzy;
+use crate::query::{Adjustment, Query};
+use std::env;
+use std::str::FromStr;
+
+// Boost for a path whose last component matches the last pattern word when
+// matching in `prefer-basename` mode.
+const BASENAME_BOOST: f64 = 4.0;
+
+pub const MATCH_MODES: &[&str] = &["anywhere", "prefer-basename", "basename"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MatchMode {
+    // Every word may match anywhere in the path (z behavior)
+    #[default]
+    Anywhere,
+    // Like Anywhere, but boost paths whose last component matches the last word
+    PreferBasename,
+    // The last word must match the last component (zoxide behavior)
+    Basename,
+}
+
+impl FromStr for MatchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anywhere" => Ok(MatchMode::Anywhere),
+            "prefer-basename" => Ok(MatchMode::PreferBasename),
+            "basename" => Ok(MatchMode::Basename),
+            _ => Err(format!("unknown match mode: {}", s)),
+        }
+    }
+}
+
+// The match mode configured through `_Z_MATCH`, falling back to anywhere.
+pub fn default_match_mode() -> MatchMode {
+    match env::var("_Z_MATCH") {
+        Ok(name) => name.parse().unwrap_or_else(|err| {
+            eprintln!("z: {} in _Z_MATCH", err);
+            MatchMode::Anywhere
+        }),
+        Err(_) => MatchMode::Anywhere,
+    }
+}
+
+// Fuzzy matching is enabled through `_Z_FUZZY=1`.
+pub fn fuzzy_enabled() -> bool {
+    env::var("_Z_FUZZY").is_ok_and(|value| value == "1")
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathMatch {
+    pub matched: Vec<String>,
+    pub adjustments: Vec<Adjustment>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pattern {
+    pub words: Vec<String>,
+    pub excluded: Vec<String>,
+    pub tags: Vec<String>,
+    pub excluded_tags: Vec<String>,
+}
+
+// Splits a pattern into words that must match, words that exclude a path
+// and tags. A word starting with `!` or `-` is an exclusion and one starting
+// with `@` a tag, so `@acme !@archived` requires one tag and rules out the
+// other. A leading backslash keeps the rest of the word literal, so
+// `\!important` matches "!important".
+pub fn parse_pattern(pattern: &str) -> Pattern {
+    let mut parsed = Pattern::default();
+    for word in pattern.split_whitespace() {
+        if let Some(literal) = word.strip_prefix('\\') {
+            if !literal.is_empty() {
+                parsed.words.push(literal.to_string());
+            }
+        } else if word.len() > 1 && (word.starts_with('!') || word.starts_with('-')) {
+            match word[1..].strip_prefix('@').filter(|tag| !tag.is_empty()) {
+                Some(tag) => parsed.excluded_tags.push(tag.to_string()),
+                None => parsed.excluded.push(word[1..].to_string()),
+            }
+        } else if let Some(tag) = word.strip_prefix('@').filter(|tag| !tag.is_empty()) {
+            parsed.tags.push(tag.to_string());
+        } else {
+            parsed.words.push(word.to_string());
+        }
+    }
+    parsed
+}
+
+pub struct Matcher {
+    words: Vec<String>,
+    excluded: Vec<String>,
+    tags: Vec<String>,
+    excluded_tags: Vec<String>,
+    mode: MatchMode,
+    fuzzy: bool,
+    ignore_accents: bool,
+}
+
+impl Matcher {
+    pub fn new(query: &Query) -> Self {
+        let pattern = parse_pattern(query.pattern);
+        let fold_all = |words: &[String]| -> Vec<String> {
+            words
+                .iter()
+                .map(|word| fold(word, query.ignore_accents))
+                .collect()
+        };
+        Self {
+            words: pattern
+                .words
+                .iter()
+                .map(|word| fold(&expand_home(word), query.ignore_accents))
+                .collect(),
+            excluded: fold_all(&pattern.excluded),
+            tags: fold_all(&pattern.tags),
+            excluded_tags: fold_all(&pattern.excluded_tags),
+            mode: query.match_mode,
+            fuzzy: query.fuzzy,
+            ignore_accents: query.ignore_accents,
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> Option<PathMatch> {
+        let path_lower = fold(path, self.ignore_accents);
+        let mut path_match = PathMatch {
+            matched: self.words.clone(),
+            adjustments: Vec::new(),
+        };
+
+        // ALL words must match the path. Words containing a slash may also
+        // match consecutive path components by prefix, as in p/r/api. In
+        // fuzzy mode the quality of the alignments scales the score.
+        let mut quality = 1.0;
+        for word in &self.words {
+            if word.contains('/') && abbreviation_matches(word, &path_lower) {
+                continue;
+            }
+            if self.fuzzy {
+                quality *= fuzzy::quality(word, path, self.ignore_accents)?;
+            } else if !path_lower.contains(word.as_str()) {
+                return None;
+            }
+        }
+        if quality < 1.0 {
+            path_match.adjustments.push(Adjustment {
+                reason: format!("fuzzy match quality of \"{}\"", self.words.join(" ")),
+                factor: quality,
+            });
+        }
+
+        let last_word = match self.words.last() {
+            // For abbreviations only the part after the last slash is
+            // expected in the last component
+            Some(word) => word
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or(word),
+            None => return Some(path_match),
+        };
+        let in_basename = if self.fuzzy {
+            fuzzy::quality(last_word, basename(&path_lower), self.ignore_accents).is_some()
+        } else {
+            basename(&path_lower).contains(last_word)
+        };
+
+        match self.mode {
+            MatchMode::Anywhere => {}
+            MatchMode::PreferBasename => {
+                if in_basename {
+                    path_match.adjustments.push(Adjustment {
+                        reason: format!("\"{}\" matches the last path component", last_word),
+                        factor: BASENAME_BOOST,
+                    });
+                }
+            }
+            MatchMode::Basename => {
+                if !in_basename {
+                    return None;
+                }
+            }
+        }
+        Some(path_match)
+    }
+
+    // The exclusion term that rules out `path`, if any.
+    pub fn excluded_by(&self, path: &str) -> Option<&str> {
+        let path_lower = fold(path, self.ignore_accents);
+        self.excluded
+            .iter()
+            .find(|word| path_lower.contains(word.as_str()))
+            .map(String::as_str)
+    }
+
+    // Whether an entry carries every tag in the pattern.
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        self.tags.is_empty() || {
+            let tags = self.fold_tags(tags);
+            self.tags.iter().all(|tag| tags.contains(tag))
+        }
+    }
+
+    // The excluded tag that rules out an entry, if any.
+    pub fn excluded_tag(&self, tags: &[String]) -> Option<&str> {
+        if self.excluded_tags.is_empty() {
+            return None;
+        }
+        let tags = self.fold_tags(tags);
+        self.excluded_tags
+            .iter()
+            .find(|tag| tags.contains(tag))
+            .map(String::as_str)
+    }
+
+    fn fold_tags(&self, tags: &[String]) -> Vec<String> {
+        tags.iter()
+            .map(|tag| fold(tag, self.ignore_accents))
+            .collect()
+    }
+}
+
+// Matches the slash-separated parts of `word` as prefixes of consecutive path
+// components. A leading slash anchors the first part at the root.
+pub fn abbreviation_matches(word: &str, path: &str) -> bool {
+    let parts: Vec<&str> = word.split('/').filter(|part| !part.is_empty()).collect();
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    if parts.len() > components.len() {
+        return false;
+    }
+
+    let last_start = if word.starts_with('/') {
+        0
+    } else {
+        components.len() - parts.len()
+    };
+    (0..=last_start).any(|start| {
+        parts
+            .iter()
+            .zip(&components[start..])
+            .all(|(part, component)| component.starts_with(part))
+    })
+}
+
+fn expand_home(word: &str) -> String {
+    if word == "~" || word.starts_with("~/") {
+        if let Some(home) = home::home_dir() {
+            return format!("{}{}", home.to_string_lossy(), &word[1..]);
+        }
+    }
+    word.to_string()
+}
+
+fn basename(path: &str) -> &str {
+    let trimmed = path.trim_end_matches('/');
+    trimmed.rsplit('/').next().unwrap_or(trimmed)
+}
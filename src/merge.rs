@@ -0,0 +1,129 @@
+use crate::aging::{Aging, Clock, SystemClock};
+use crate::database::ZDatabase;
+use crate::entry::DirEntry;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergePolicy {
+    Sum,
+    Max,
+    Weighted(f64),
+}
+
+impl FromStr for MergePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(MergePolicy::Sum),
+            "max" => Ok(MergePolicy::Max),
+            "weighted" => Ok(MergePolicy::Weighted(0.5)),
+            _ => Err(format!("unknown merge policy: {}", s)),
+        }
+    }
+}
+
+impl MergePolicy {
+    fn combine(&self, existing: f64, incoming: f64) -> f64 {
+        match self {
+            MergePolicy::Sum => existing + incoming,
+            MergePolicy::Max => existing.max(incoming),
+            MergePolicy::Weighted(weight) => existing + weight * incoming,
+        }
+    }
+
+    fn initial(&self, incoming: f64) -> f64 {
+        match self {
+            MergePolicy::Weighted(weight) => weight * incoming,
+            _ => incoming,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    pub added: Vec<DirEntry>,
+    pub updated: Vec<(DirEntry, DirEntry)>,
+    pub aged_out: Vec<DirEntry>,
+}
+
+impl MergeReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.aged_out.is_empty()
+    }
+}
+
+impl ZDatabase {
+    pub fn merge<I>(&mut self, incoming: I, policy: MergePolicy) -> MergeReport
+    where
+        I: IntoIterator<Item = DirEntry>,
+    {
+        self.merge_at(incoming, policy, Aging::from_env(), &SystemClock)
+    }
+
+    // Merges at the time given by `clock`. Existing entries are decayed
+    // before the incoming ones arrive, so those aren't aged for time they
+    // weren't here. Nothing is written; the caller saves the database
+    // unless this is a dry run.
+    pub fn merge_at<I>(
+        &mut self,
+        incoming: I,
+        policy: MergePolicy,
+        aging: Aging,
+        clock: &dyn Clock,
+    ) -> MergeReport
+    where
+        I: IntoIterator<Item = DirEntry>,
+    {
+        let mut added: Vec<String> = Vec::new();
+        let mut before: HashMap<String, DirEntry> = HashMap::new();
+        let aged_out = self.change_and_age(aging, clock, |db| {
+            db.combine(incoming, policy, &mut added, &mut before)
+        });
+
+        let mut report = MergeReport {
+            added: added
+                .iter()
+                .filter_map(|path| self.entries.get(path).cloned())
+                .collect(),
+            updated: before
+                .into_values()
+                .filter_map(|old| self.entries.get(&old.path).cloned().map(|new| (old, new)))
+                .collect(),
+            aged_out,
+        };
+        report.added.sort_by(|a, b| a.path.cmp(&b.path));
+        report.updated.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+        report
+    }
+
+    // Combines incoming entries with existing ones, noting the paths added
+    // and the previous state of the ones updated.
+    fn combine<I>(
+        &mut self,
+        incoming: I,
+        policy: MergePolicy,
+        added: &mut Vec<String>,
+        before: &mut HashMap<String, DirEntry>,
+    ) where
+        I: IntoIterator<Item = DirEntry>,
+    {
+        for other in incoming {
+            if let Some(entry) = self.entries.get_mut(&other.path) {
+                if !added.contains(&other.path) {
+                    before
+                        .entry(other.path.clone())
+                        .or_insert_with(|| entry.clone());
+                }
+                entry.rank = policy.combine(entry.rank, other.rank);
+                entry.time = entry.time.max(other.time);
+            } else {
+                let entry =
+                    DirEntry::new(other.path.clone(), policy.initial(other.rank), other.time);
+                self.entries.insert(other.path.clone(), entry);
+                added.push(other.path);
+            }
+        }
+    }
+}
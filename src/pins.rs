@@ -0,0 +1,75 @@
+use crate::database::ZDatabase;
+use crate::sidecar;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+// Keyword aliases that always resolve to a directory, ahead of any
+// frecency matching.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pins {
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Pins {
+    // Aliases are single pattern words, so they can't contain whitespace or
+    // the separator and can't look like an exclusion or a flag.
+    pub fn validate_alias(alias: &str) -> Result<(), String> {
+        if alias.is_empty()
+            || alias.contains(char::is_whitespace)
+            || alias.contains('|')
+            || alias.starts_with(['-', '!', '\\'])
+        {
+            return Err(format!("invalid alias: {}", alias));
+        }
+        Ok(())
+    }
+
+    // Pins `alias` to `path`, returning the path it was pinned to before.
+    pub fn pin(&mut self, alias: &str, path: &str) -> Result<Option<String>, String> {
+        Self::validate_alias(alias)?;
+        Ok(self.aliases.insert(alias.to_string(), path.to_string()))
+    }
+
+    pub fn unpin(&mut self, alias: &str) -> Option<String> {
+        self.aliases.remove(alias)
+    }
+
+    // The pinned directory for a pattern that is exactly an alias.
+    pub fn resolve(&self, pattern: &str) -> Option<&str> {
+        self.aliases.get(pattern.trim()).map(String::as_str)
+    }
+
+    pub fn aliases_for(&self, path: &str) -> Vec<&str> {
+        self.aliases
+            .iter()
+            .filter(|(_, pinned)| pinned.as_str() == path)
+            .map(|(alias, _)| alias.as_str())
+            .collect()
+    }
+}
+
+impl ZDatabase {
+    pub fn pins_file(&self) -> PathBuf {
+        self.sidecar_file("pins")
+    }
+
+    pub fn load_pins(&self) -> Pins {
+        let mut pins = Pins::default();
+        for line in sidecar::read_lines(&self.pins_file()) {
+            if let Some((alias, path)) = line.split_once('|') {
+                pins.aliases.insert(alias.to_string(), path.to_string());
+            }
+        }
+        pins
+    }
+
+    pub fn save_pins(&self, pins: &Pins) -> io::Result<()> {
+        let lines: Vec<String> = pins
+            .aliases
+            .iter()
+            .map(|(alias, path)| format!("{}|{}", alias, path))
+            .collect();
+        sidecar::write_lines(&self.pins_file(), &lines)
+    }
+}
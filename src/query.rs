@@ -0,0 +1,303 @@
+use crate::database::ZDatabase;
+use crate::entry::DirEntry;
+use crate::fold::fold;
+use crate::learn::Learned;
+use crate::matcher::{MatchMode, Matcher};
+use crate::scorer::Scorer;
+use crate::vcs;
+use std::env;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Jumping skips the working directory unless `_Z_EXCLUDE_CWD=0`.
+pub fn cwd_excluded() -> bool {
+    env::var("_Z_EXCLUDE_CWD").map_or(true, |value| value != "0")
+}
+
+// Proximity weight configured through `_Z_PROXIMITY`, off by default.
+pub fn default_proximity_weight() -> f64 {
+    match env::var("_Z_PROXIMITY") {
+        Ok(value) => match value.parse::<f64>() {
+            Ok(weight) if weight >= 0.0 => weight,
+            _ => {
+                eprintln!("z: invalid weight {} in _Z_PROXIMITY", value);
+                0.0
+            }
+        },
+        Err(_) => 0.0,
+    }
+}
+
+pub struct Query<'a> {
+    pub pattern: &'a str,
+    // Only this directory and its subdirectories are candidates (-c, --scope)
+    pub current_dir: Option<&'a str>,
+    pub scorer: &'a dyn Scorer,
+    pub match_mode: MatchMode,
+    pub fuzzy: bool,
+    pub ignore_accents: bool,
+    // A directory that is never a candidate, such as the one being left
+    pub exclude_dir: Option<&'a str>,
+    // Factor applied to the scores of repository roots
+    pub repo_boost: Option<f64>,
+    // Only repository roots are candidates
+    pub repo_only: bool,
+    // Candidates sharing a longer path prefix with this directory get a
+    // boost of up to 1 + proximity_weight
+    pub near_dir: Option<&'a str>,
+    pub proximity_weight: f64,
+    // Earlier jumps for this or longer queries boost the chosen paths
+    pub learned: Option<&'a Learned>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(pattern: &'a str, scorer: &'a dyn Scorer) -> Self {
+        Self {
+            pattern,
+            current_dir: None,
+            scorer,
+            match_mode: MatchMode::default(),
+            fuzzy: false,
+            ignore_accents: false,
+            exclude_dir: None,
+            repo_boost: None,
+            repo_only: false,
+            near_dir: None,
+            proximity_weight: 0.0,
+            learned: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Adjustment {
+    pub reason: String,
+    pub factor: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub entry: DirEntry,
+    pub score: f64,
+    pub base_score: f64,
+    pub matched: Vec<String>,
+    pub adjustments: Vec<Adjustment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Exclusion {
+    pub entry: DirEntry,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub now: u64,
+    pub candidates: Vec<Candidate>,
+    pub excluded: Vec<Exclusion>,
+}
+
+impl SearchResult {
+    // Names the rule that put the first candidate ahead of the second one.
+    pub fn decided_by(&self, scorer: &dyn Scorer) -> Option<String> {
+        let winner = self.candidates.first()?;
+        let runner_up = match self.candidates.get(1) {
+            Some(runner_up) => runner_up,
+            None => return Some("only matching candidate".to_string()),
+        };
+
+        if winner.score > runner_up.score && winner.base_score <= runner_up.base_score {
+            let reasons: Vec<&str> = winner
+                .adjustments
+                .iter()
+                .chain(&runner_up.adjustments)
+                .map(|adjustment| adjustment.reason.as_str())
+                .collect();
+            Some(format!(
+                "adjusted score ({:.2} vs {:.2} for {}): {}",
+                winner.score,
+                runner_up.score,
+                runner_up.entry.path,
+                reasons.join("; ")
+            ))
+        } else if winner.score > runner_up.score {
+            Some(format!(
+                "highest {} score ({:.2} vs {:.2} for {})",
+                scorer.name(),
+                winner.score,
+                runner_up.score,
+                runner_up.entry.path
+            ))
+        } else {
+            Some(format!(
+                "tied {} score with {}, broken by path order",
+                scorer.name(),
+                runner_up.entry.path
+            ))
+        }
+    }
+}
+
+impl ZDatabase {
+    pub fn search(&self, query: &Query) -> SearchResult {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let matcher = Matcher::new(query);
+        let current_lower = query
+            .current_dir
+            .map(|current| fold(current, query.ignore_accents));
+
+        let mut candidates = Vec::new();
+        let mut excluded = Vec::new();
+        for entry in self.entries.values() {
+            let path_match = match matcher.matches(&entry.path) {
+                Some(path_match) => path_match,
+                None => continue,
+            };
+
+            if query
+                .exclude_dir
+                .is_some_and(|dir| same_dir(dir, &entry.path))
+            {
+                excluded.push(Exclusion {
+                    entry: entry.clone(),
+                    reason: "is the current directory".to_string(),
+                });
+                continue;
+            }
+
+            if !matcher.has_tags(&entry.tags) {
+                continue;
+            }
+            if let Some(tag) = matcher.excluded_tag(&entry.tags) {
+                excluded.push(Exclusion {
+                    entry: entry.clone(),
+                    reason: format!("tagged @{}", tag),
+                });
+                continue;
+            }
+
+            if let Some(word) = matcher.excluded_by(&entry.path) {
+                excluded.push(Exclusion {
+                    entry: entry.clone(),
+                    reason: format!("contains excluded word \"{}\"", word),
+                });
+                continue;
+            }
+
+            if let (Some(current), Some(current_dir)) = (&current_lower, query.current_dir) {
+                if !within(current, &fold(&entry.path, query.ignore_accents)) {
+                    excluded.push(Exclusion {
+                        entry: entry.clone(),
+                        reason: format!("outside current directory {}", current_dir),
+                    });
+                    continue;
+                }
+            }
+
+            let mut adjustments = path_match.adjustments;
+            if query.repo_only || query.repo_boost.is_some() {
+                let marker = vcs::root_marker(Path::new(&entry.path));
+                match (marker, query.repo_boost) {
+                    (None, _) if query.repo_only => {
+                        excluded.push(Exclusion {
+                            entry: entry.clone(),
+                            reason: "not a repository root".to_string(),
+                        });
+                        continue;
+                    }
+                    (Some(marker), Some(boost)) => adjustments.push(Adjustment {
+                        reason: format!("repository root ({})", marker),
+                        factor: boost,
+                    }),
+                    _ => {}
+                }
+            }
+
+            if let Some(near_dir) = query.near_dir.filter(|_| query.proximity_weight > 0.0) {
+                let (shared, total) = shared_components(near_dir, &entry.path);
+                if shared > 0 {
+                    adjustments.push(Adjustment {
+                        reason: format!(
+                            "shares {} of {} path components with {}",
+                            shared, total, near_dir
+                        ),
+                        factor: 1.0 + query.proximity_weight * shared as f64 / total as f64,
+                    });
+                }
+            }
+
+            if let Some((factor, reason)) = query
+                .learned
+                .and_then(|learned| learned.boost(query.pattern, &entry.path))
+            {
+                adjustments.push(Adjustment { reason, factor });
+            }
+
+            if entry.weight != 1.0 {
+                adjustments.push(Adjustment {
+                    reason: "manual weight".to_string(),
+                    factor: entry.weight,
+                });
+            }
+
+            let base_score = query.scorer.score(entry, now);
+            let factor: f64 = adjustments.iter().map(|a| a.factor).product();
+            candidates.push(Candidate {
+                entry: entry.clone(),
+                score: base_score * factor,
+                base_score,
+                matched: path_match.matched,
+                adjustments,
+            });
+        }
+
+        candidates.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.entry.path.cmp(&b.entry.path))
+        });
+        excluded.sort_by(|a, b| a.entry.path.cmp(&b.entry.path));
+
+        SearchResult {
+            now,
+            candidates,
+            excluded,
+        }
+    }
+}
+
+// Whether `path` is `dir` or below it, comparing whole components so that
+// /work/app2 is not inside /work/app.
+fn within(dir: &str, path: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    match path.strip_prefix(dir) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+// The number of leading components `path` has in common with `dir`, and the
+// number of components of `dir`.
+fn shared_components(dir: &str, path: &str) -> (usize, usize) {
+    let dir: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+    let shared = dir
+        .iter()
+        .zip(path.split('/').filter(|c| !c.is_empty()))
+        .take_while(|(a, b)| *a == b)
+        .count();
+    (shared, dir.len())
+}
+
+// Compares directories ignoring trailing slashes.
+fn same_dir(a: &str, b: &str) -> bool {
+    fn trim(path: &str) -> &str {
+        match path.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        }
+    }
+    trim(a) == trim(b)
+}
@@ -0,0 +1,206 @@
+use crate::entry::DirEntry;
+use crate::histogram::{local_slot, WEEKDAYS};
+use std::env;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+pub const SCORERS: &[&str] = &["frecency", "rank", "recent", "zoxide", "routine", "history"];
+
+pub trait Scorer {
+    fn name(&self) -> &'static str;
+    fn score(&self, entry: &DirEntry, now: u64) -> f64;
+
+    fn explain(&self, entry: &DirEntry, now: u64) -> String {
+        format!("{:.2}", self.score(entry, now))
+    }
+}
+
+// The original z frecency: rank weighted by a smooth recency falloff.
+pub struct Frecency;
+
+impl Scorer for Frecency {
+    fn name(&self) -> &'static str {
+        "frecency"
+    }
+
+    fn score(&self, entry: &DirEntry, now: u64) -> f64 {
+        entry.frecency_at(now)
+    }
+
+    fn explain(&self, entry: &DirEntry, now: u64) -> String {
+        format!(
+            "10000 * rank {} * 3.75 / (0.0001 * age {}s + 1.25) = {:.2}",
+            entry.rank,
+            now.saturating_sub(entry.time),
+            self.score(entry, now)
+        )
+    }
+}
+
+pub struct Rank;
+
+impl Scorer for Rank {
+    fn name(&self) -> &'static str {
+        "rank"
+    }
+
+    fn score(&self, entry: &DirEntry, _now: u64) -> f64 {
+        entry.rank
+    }
+
+    fn explain(&self, entry: &DirEntry, _now: u64) -> String {
+        format!("rank {}", entry.rank)
+    }
+}
+
+pub struct Recent;
+
+impl Scorer for Recent {
+    fn name(&self) -> &'static str {
+        "recent"
+    }
+
+    fn score(&self, entry: &DirEntry, _now: u64) -> f64 {
+        entry.time as f64
+    }
+
+    fn explain(&self, entry: &DirEntry, now: u64) -> String {
+        format!(
+            "last visit at {} ({}s ago)",
+            entry.time,
+            now.saturating_sub(entry.time)
+        )
+    }
+}
+
+// zoxide's bucketed recency: rank multiplied by 4 within the last hour,
+// 2 within a day, 0.5 within a week and 0.25 after that.
+pub struct Zoxide;
+
+impl Scorer for Zoxide {
+    fn name(&self) -> &'static str {
+        "zoxide"
+    }
+
+    fn score(&self, entry: &DirEntry, now: u64) -> f64 {
+        entry.rank * zoxide_bucket(now.saturating_sub(entry.time)).0
+    }
+
+    fn explain(&self, entry: &DirEntry, now: u64) -> String {
+        let (factor, bucket) = zoxide_bucket(now.saturating_sub(entry.time));
+        format!(
+            "rank {} * {} (visited {}) = {:.2}",
+            entry.rank,
+            factor,
+            bucket,
+            self.score(entry, now)
+        )
+    }
+}
+
+fn zoxide_bucket(age: u64) -> (f64, &'static str) {
+    if age < HOUR {
+        (4.0, "within the last hour")
+    } else if age < DAY {
+        (2.0, "within the last day")
+    } else if age < WEEK {
+        (0.5, "within the last week")
+    } else {
+        (0.25, "over a week ago")
+    }
+}
+
+// Frecency boosted by how often the entry was visited around the current
+// hour and on the current weekday, by up to 3x for a perfect match.
+pub struct Routine;
+
+impl Scorer for Routine {
+    fn name(&self) -> &'static str {
+        "routine"
+    }
+
+    fn score(&self, entry: &DirEntry, now: u64) -> f64 {
+        let (hour_share, weekday_share) = entry.visits.similarity(now);
+        entry.frecency_at(now) * (1.0 + hour_share + weekday_share)
+    }
+
+    fn explain(&self, entry: &DirEntry, now: u64) -> String {
+        let (hour_share, weekday_share) = entry.visits.similarity(now);
+        let (hour, weekday) = local_slot(now);
+        format!(
+            "frecency {:.2} * (1 + {:.2} of visits around {:02}:00 + {:.2} on {}) = {:.2}",
+            entry.frecency_at(now),
+            hour_share,
+            hour,
+            weekday_share,
+            WEEKDAYS[weekday],
+            self.score(entry, now)
+        )
+    }
+}
+
+// The true visit count weighted by the zoxide buckets of the most recent
+// visits, so a burst of recent visits counts more than old ones. Entries
+// without a visit history are scored like zoxide.
+pub struct History;
+
+impl Scorer for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn score(&self, entry: &DirEntry, now: u64) -> f64 {
+        match &entry.history {
+            Some(history) if !history.recent.is_empty() => {
+                history.visits as f64 * recent_weight(&history.recent, now)
+            }
+            _ => Zoxide.score(entry, now),
+        }
+    }
+
+    fn explain(&self, entry: &DirEntry, now: u64) -> String {
+        match &entry.history {
+            Some(history) if !history.recent.is_empty() => format!(
+                "{} visits * mean weight {:.2} of the last {} visits = {:.2}",
+                history.visits,
+                recent_weight(&history.recent, now),
+                history.recent.len(),
+                self.score(entry, now)
+            ),
+            _ => format!("no visit history, {}", Zoxide.explain(entry, now)),
+        }
+    }
+}
+
+fn recent_weight(recent: &[u64], now: u64) -> f64 {
+    let total: f64 = recent
+        .iter()
+        .map(|time| zoxide_bucket(now.saturating_sub(*time)).0)
+        .sum();
+    total / recent.len() as f64
+}
+
+pub fn scorer_by_name(name: &str) -> Option<Box<dyn Scorer>> {
+    match name {
+        "frecency" => Some(Box::new(Frecency)),
+        "rank" => Some(Box::new(Rank)),
+        "recent" => Some(Box::new(Recent)),
+        "zoxide" => Some(Box::new(Zoxide)),
+        "routine" => Some(Box::new(Routine)),
+        "history" => Some(Box::new(History)),
+        _ => None,
+    }
+}
+
+// The scorer configured through `_Z_SCORER`, falling back to frecency.
+pub fn default_scorer() -> Box<dyn Scorer> {
+    match env::var("_Z_SCORER") {
+        Ok(name) => scorer_by_name(&name).unwrap_or_else(|| {
+            eprintln!("z: unknown scorer in _Z_SCORER: {}", name);
+            Box::new(Frecency)
+        }),
+        Err(_) => Box::new(Frecency),
+    }
+}
@@ -0,0 +1,40 @@
+use crate::database::ZDatabase;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+// Reads the lines of a sidecar, or none when it doesn't exist yet.
+pub fn read_lines(path: &Path) -> Vec<String> {
+    match File::open(path) {
+        Ok(file) => BufReader::new(file).lines().map_while(Result::ok).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Replaces the contents of a sidecar, removing the file when there is
+// nothing left to store.
+pub fn write_lines(path: &Path, lines: &[String]) -> io::Result<()> {
+    if lines.is_empty() {
+        return match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        };
+    }
+
+    let mut file = File::create(path)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+impl ZDatabase {
+    // Data the z format has no room for lives next to the datafile, so that
+    // the datafile itself stays readable by z and other ports.
+    pub fn sidecar_file(&self, extension: &str) -> PathBuf {
+        let mut name = self.data_file.clone().into_os_string();
+        name.push(".");
+        name.push(extension);
+        PathBuf::from(name)
+    }
+}
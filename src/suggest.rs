@@ -0,0 +1,211 @@
+use crate::database::ZDatabase;
+use crate::entry::DirEntry;
+use crate::fold::fold;
+use crate::matcher;
+use crate::query::Query;
+use std::collections::BTreeSet;
+use std::env;
+
+const MAX_SUGGESTIONS: usize = 5;
+const MAX_CORRECTIONS_PER_WORD: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub pattern: String,
+    pub distance: usize,
+    pub target: DirEntry,
+}
+
+// Jumping to a lone suggestion is enabled through `_Z_AUTOCORRECT=1`.
+pub fn autocorrect_enabled() -> bool {
+    env::var("_Z_AUTOCORRECT").is_ok_and(|value| value == "1")
+}
+
+// How many edits a word may be away from a path component. Very short
+// words are only corrected to components of a similar length, since almost
+// anything contains something one edit away from them.
+fn max_distance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        6..=8 => 2,
+        _ => 3,
+    }
+}
+
+// Optimal string alignment distance: Levenshtein plus transpositions of
+// adjacent characters, the most common typo when typing fast.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    alignment_distance(a, b, false)
+}
+
+// Edit distance between `needle` and its best-matching substring of
+// `haystack`, mirroring how pattern words match anywhere inside a path.
+pub fn substring_distance(needle: &str, haystack: &str) -> usize {
+    alignment_distance(needle, haystack, true)
+}
+
+fn alignment_distance(needle: &str, haystack: &str, substring: bool) -> usize {
+    let needle: Vec<char> = needle.chars().collect();
+    let haystack: Vec<char> = haystack.chars().collect();
+    let width = haystack.len() + 1;
+
+    // With substring matching the haystack prefix before the match is free
+    let mut previous: Vec<usize> = vec![0; width];
+    let mut current: Vec<usize> = (0..width).map(|j| if substring { 0 } else { j }).collect();
+    for i in 1..=needle.len() {
+        let before_previous = std::mem::replace(&mut previous, current);
+        current = vec![i; width];
+        for j in 1..width {
+            let cost = usize::from(needle[i - 1] != haystack[j - 1]);
+            current[j] = (previous[j - 1] + cost)
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1);
+            if i > 1
+                && j > 1
+                && needle[i - 1] == haystack[j - 2]
+                && needle[i - 2] == haystack[j - 1]
+            {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+    }
+
+    if substring {
+        current.into_iter().min().unwrap_or(0)
+    } else {
+        current[haystack.len()]
+    }
+}
+
+fn component_distance(word: &str, component: &str) -> usize {
+    if word.chars().count() > 3 {
+        substring_distance(word, component)
+    } else {
+        edit_distance(word, component)
+    }
+}
+
+// Keeps a corrected word literal when it would otherwise read as an
+// exclusion or a tag.
+fn escape(word: &str) -> String {
+    if word.starts_with(['!', '-', '@', '\\']) {
+        format!("\\{}", word)
+    } else {
+        word.to_string()
+    }
+}
+
+impl ZDatabase {
+    pub fn suggest(&self, query: &Query) -> Vec<Suggestion> {
+        let components: BTreeSet<String> = self
+            .entries
+            .keys()
+            .flat_map(|path| path.split('/'))
+            .filter(|component| !component.is_empty())
+            .map(|component| fold(component, query.ignore_accents))
+            .collect();
+        let pattern = matcher::parse_pattern(query.pattern);
+        let words: Vec<String> = pattern
+            .words
+            .iter()
+            .map(|word| fold(word, query.ignore_accents))
+            .collect();
+        // Tags and exclusion terms are carried over to every suggestion
+        // unchanged
+        let exclusions: String = pattern
+            .tags
+            .iter()
+            .map(|tag| format!(" @{}", tag))
+            .chain(pattern.excluded.iter().map(|word| format!(" !{}", word)))
+            .chain(
+                pattern
+                    .excluded_tags
+                    .iter()
+                    .map(|tag| format!(" !@{}", tag)),
+            )
+            .collect();
+
+        let mut patterns: Vec<(String, usize)> = Vec::new();
+
+        // Path components: replace each word found in no path with the
+        // components closest to it
+        let mut combinations: Vec<(Vec<String>, usize)> = vec![(Vec::new(), 0)];
+        for word in &words {
+            let corrections: Vec<(String, usize)> = if self
+                .entries
+                .keys()
+                .any(|path| fold(path, query.ignore_accents).contains(word.as_str()))
+            {
+                vec![(word.clone(), 0)]
+            } else {
+                let mut close: Vec<(String, usize)> = components
+                    .iter()
+                    .map(|component| (component.clone(), component_distance(word, component)))
+                    .filter(|(_, distance)| *distance <= max_distance(word))
+                    .collect();
+                close.sort_by_key(|(_, distance)| *distance);
+                close.truncate(MAX_CORRECTIONS_PER_WORD);
+                close
+            };
+            combinations = combinations
+                .into_iter()
+                .flat_map(|(prefix, distance)| {
+                    corrections.iter().map(move |(correction, extra)| {
+                        let mut words = prefix.clone();
+                        words.push(correction.clone());
+                        (words, distance + extra)
+                    })
+                })
+                .collect();
+        }
+        patterns.extend(
+            combinations
+                .into_iter()
+                .filter(|(_, distance)| *distance > 0)
+                .map(|(words, distance)| {
+                    let words: Vec<String> = words.iter().map(|word| escape(word)).collect();
+                    (words.join(" "), distance)
+                }),
+        );
+
+        // Whole pattern: a multi-word pattern may be a split or misspelled
+        // single component, as in "my proj" for "my-project"
+        if words.len() > 1 {
+            let joined = words.join("");
+            for component in &components {
+                let distance = edit_distance(&joined, component);
+                if distance <= max_distance(&joined) {
+                    patterns.push((escape(component), distance.max(1)));
+                }
+            }
+        }
+
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+        for (pattern, distance) in patterns {
+            let pattern = pattern + &exclusions;
+            if suggestions.iter().any(|s| s.pattern == pattern) {
+                continue;
+            }
+            let corrected = Query {
+                pattern: &pattern,
+                ..*query
+            };
+            if let Some(best) = self.search(&corrected).candidates.into_iter().next() {
+                suggestions.push(Suggestion {
+                    pattern,
+                    distance,
+                    target: best.entry,
+                });
+            }
+        }
+
+        suggestions.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then_with(|| a.pattern.cmp(&b.pattern))
+        });
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+}
@@ -0,0 +1,77 @@
+use crate::database::ZDatabase;
+use crate::sidecar;
+use std::io;
+use std::path::PathBuf;
+
+// Normalizes a tag given on the command line, where the leading `@` used in
+// patterns is optional.
+pub fn parse_tag(tag: &str) -> Result<String, String> {
+    let name = tag.strip_prefix('@').unwrap_or(tag);
+    if name.is_empty()
+        || name.contains(char::is_whitespace)
+        || name.contains([',', '|', '@'])
+        || name.starts_with(['-', '!', '\\'])
+    {
+        return Err(format!("invalid tag: {}", tag));
+    }
+    Ok(name.to_string())
+}
+
+impl ZDatabase {
+    pub fn tags_file(&self) -> PathBuf {
+        self.sidecar_file("tags")
+    }
+
+    // Attaches the tags in the sidecar to loaded entries.
+    pub fn load_tags(&mut self) {
+        for line in sidecar::read_lines(&self.tags_file()) {
+            if let Some((path, tags)) = line.rsplit_once('|') {
+                if let Some(entry) = self.entries.get_mut(path) {
+                    entry.tags = tags.split(',').map(String::from).collect();
+                }
+            }
+        }
+    }
+
+    pub fn save_tags(&self) -> io::Result<()> {
+        let mut entries: Vec<_> = self
+            .entries
+            .values()
+            .filter(|entry| !entry.tags.is_empty())
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let lines: Vec<String> = entries
+            .into_iter()
+            .map(|entry| format!("{}|{}", entry.path, entry.tags.join(",")))
+            .collect();
+        sidecar::write_lines(&self.tags_file(), &lines)
+    }
+
+    // Adds tags to a tracked directory, returning false when it isn't one.
+    pub fn tag(&mut self, path: &str, tags: &[String]) -> bool {
+        match self.entries.get_mut(path) {
+            Some(entry) => {
+                for tag in tags {
+                    if !entry.tags.contains(tag) {
+                        entry.tags.push(tag.clone());
+                    }
+                }
+                entry.tags.sort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Removes tags from a directory, returning the ones it had.
+    pub fn untag(&mut self, path: &str, tags: &[String]) -> Vec<String> {
+        match self.entries.get_mut(path) {
+            Some(entry) => {
+                let (removed, kept) = entry.tags.drain(..).partition(|tag| tags.contains(tag));
+                entry.tags = kept;
+                removed
+            }
+            None => Vec::new(),
+        }
+    }
+}
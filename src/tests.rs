@@ -1,7 +1,23 @@
-use crate::{DirEntry, ZDatabase};
-use std::collections::HashMap;
+use crate::aging::{decay_factor, parse_half_life, Aging, FixedClock, DECAY_FLOOR};
+use crate::dwell::{dwell_increment, DWELL_UNIT, MAX_INCREMENT, MIN_INCREMENT};
+use crate::export::export;
+use crate::fold::fold;
+use crate::fuzzy;
+use crate::histogram::{local_slot, VisitHistogram};
+use crate::history::{VisitHistory, RECENT_VISITS};
+use crate::import::{
+    parse_autojump, parse_fasd, parse_zoxide_db, parse_zoxide_query, partition_directories,
+};
+use crate::learn::{Learned, MAX_SELECTIONS};
+use crate::matcher::{abbreviation_matches, parse_pattern, Pattern};
+use crate::pins::Pins;
+use crate::scorer::{scorer_by_name, Frecency, History, Rank, Routine, Zoxide};
+use crate::suggest::{edit_distance, substring_distance};
+use crate::tags::parse_tag;
+use crate::vcs::{self, Scope};
+use crate::weight::parse_weight;
+use crate::{DirEntry, ExportFormat, ImportMode, MatchMode, MergePolicy, Query, Scorer, ZDatabase};
 use std::fs;
-use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn create_test_db() -> ZDatabase {
@@ -12,10 +28,7 @@ fn create_test_db() -> ZDatabase {
             .unwrap()
             .as_nanos()
     );
-    let mut db = ZDatabase {
-        entries: HashMap::new(),
-        data_file: PathBuf::from(&temp_file),
-    };
+    let mut db = ZDatabase::with_data_file(&temp_file);
 
     // Add some test entries
     let now = SystemTime::now()
@@ -46,7 +59,7 @@ fn test_dir_entry_frecency() {
         .as_secs();
     let entry = DirEntry::new("/test/path".to_string(), 10.0, now - 1000);
     let frecency = entry.frecency();
-    assert!(frecency > 0);
+    assert!(frecency > 0.0);
 
     // More recent entry should have higher frecency than older one with same rank
     let recent_entry = DirEntry::new("/test/path2".to_string(), 10.0, now - 100);
@@ -185,10 +198,7 @@ fn test_save_and_load() {
             .unwrap()
             .as_nanos()
     );
-    let mut db = ZDatabase {
-        entries: HashMap::new(),
-        data_file: PathBuf::from(&temp_file),
-    };
+    let mut db = ZDatabase::with_data_file(&temp_file);
 
     // Add some test data
     let now = SystemTime::now()
@@ -208,10 +218,7 @@ fn test_save_and_load() {
     db.save();
 
     // Create new database and load
-    let mut db2 = ZDatabase {
-        entries: HashMap::new(),
-        data_file: PathBuf::from(&temp_file),
-    };
+    let mut db2 = ZDatabase::with_data_file(&temp_file);
     db2.load();
 
     // Should have same entries
@@ -306,3 +313,1500 @@ fn test_no_matches() {
 
     assert_eq!(matches.len(), 0);
 }
+
+#[test]
+fn test_merge_sum_policy() {
+    let mut db = create_test_db();
+    let projects_time = db.entries["/home/user/projects"].time;
+
+    let report = db.merge(
+        vec![
+            DirEntry::new("/home/user/projects".to_string(), 2.0, projects_time - 100),
+            DirEntry::new("/srv/shared".to_string(), 4.0, 1640995200),
+        ],
+        MergePolicy::Sum,
+    );
+
+    assert_eq!(db.entries["/home/user/projects"].rank, 7.0);
+    // The newest timestamp wins
+    assert_eq!(db.entries["/home/user/projects"].time, projects_time);
+    assert_eq!(db.entries["/srv/shared"].rank, 4.0);
+    assert_eq!(report.added.len(), 1);
+    assert_eq!(report.added[0].path, "/srv/shared");
+    assert_eq!(report.updated.len(), 1);
+    assert_eq!(report.updated[0].0.rank, 5.0);
+    assert_eq!(report.updated[0].1.rank, 7.0);
+}
+
+#[test]
+fn test_merge_max_and_weighted_policies() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    db.merge(
+        vec![DirEntry::new("/home/user/projects".to_string(), 9.0, now)],
+        MergePolicy::Max,
+    );
+    assert_eq!(db.entries["/home/user/projects"].rank, 9.0);
+    assert_eq!(db.entries["/home/user/projects"].time, now);
+
+    db.merge(
+        vec![
+            DirEntry::new("/home/user/documents".to_string(), 4.0, now),
+            DirEntry::new("/new/path".to_string(), 4.0, now),
+        ],
+        MergePolicy::Weighted(0.5),
+    );
+    assert_eq!(db.entries["/home/user/documents"].rank, 5.0);
+    assert_eq!(db.entries["/new/path"].rank, 2.0);
+}
+
+#[test]
+fn test_merge_applies_aging_afterwards() {
+    let mut db = create_test_db();
+    let incoming: Vec<DirEntry> = (0..3000)
+        .map(|i| DirEntry::new(format!("/test/merge/{}", i), 3.0, 1640995200))
+        .chain(std::iter::once(DirEntry::new(
+            "/test/merge/weak".to_string(),
+            1.0,
+            1640995200,
+        )))
+        .collect();
+
+    let report = db.merge(incoming, MergePolicy::Sum);
+
+    assert!(!db.entries.contains_key("/test/merge/weak"));
+    assert!(report.aged_out.iter().any(|e| e.path == "/test/merge/weak"));
+    for entry in db.entries.values() {
+        assert!(entry.rank >= 1.0);
+    }
+}
+
+fn zoxide_db_bytes(dirs: &[(&str, f64, u64)]) -> Vec<u8> {
+    let mut bytes = 3u32.to_le_bytes().to_vec();
+    bytes.extend((dirs.len() as u64).to_le_bytes());
+    for (path, rank, time) in dirs {
+        bytes.extend((path.len() as u64).to_le_bytes());
+        bytes.extend(path.as_bytes());
+        bytes.extend(rank.to_le_bytes());
+        bytes.extend(time.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn test_parse_zoxide_db() {
+    let bytes = zoxide_db_bytes(&[
+        ("/home/user/projects", 12.5, 1640995200),
+        ("/srv/www", 2.0, 1640995100),
+    ]);
+
+    let entries = parse_zoxide_db(&bytes).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, "/home/user/projects");
+    assert_eq!(entries[0].rank, 12.5);
+    assert_eq!(entries[0].time, 1640995200);
+    assert_eq!(entries[1].path, "/srv/www");
+
+    // Truncated and unknown-version databases are rejected
+    assert!(parse_zoxide_db(&bytes[..bytes.len() - 3]).is_err());
+    let mut wrong_version = bytes.clone();
+    wrong_version[0] = 2;
+    assert!(parse_zoxide_db(&wrong_version).is_err());
+}
+
+#[test]
+fn test_parse_zoxide_query() {
+    let text = "  48.0 /home/user/projects\n   4.0 /path with spaces\ngarbage\n";
+
+    let (entries, skipped) = parse_zoxide_query(text, 1640995200);
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, "/home/user/projects");
+    assert_eq!(entries[0].rank, 12.0);
+    assert_eq!(entries[0].time, 1640995200);
+    assert_eq!(entries[1].path, "/path with spaces");
+    assert_eq!(skipped, vec!["garbage".to_string()]);
+}
+
+#[test]
+fn test_import_merge_and_replace() {
+    let mut db = create_test_db();
+    let imported = vec![
+        DirEntry::new("/home/user/projects".to_string(), 2.0, 1640995200),
+        DirEntry::new("/srv/www".to_string(), 3.0, 1640995200),
+    ];
+
+    let report = db.import(imported.clone(), ImportMode::Merge);
+    assert_eq!(db.entries.len(), 4);
+    assert_eq!(db.entries["/home/user/projects"].rank, 7.0);
+    assert_eq!(report.merge.added.len(), 1);
+    assert_eq!(report.merge.updated.len(), 1);
+
+    let mut db = create_test_db();
+    let report = db.import(imported, ImportMode::Replace);
+    assert_eq!(db.entries.len(), 2);
+    assert_eq!(db.entries["/home/user/projects"].rank, 2.0);
+    assert_eq!(report.replaced.len(), 2);
+}
+
+#[test]
+fn test_parse_autojump() {
+    let text = "10.0\t/home/user/projects\n31.6\t/srv/www\nnot-a-weight\t/tmp\n";
+
+    let (entries, skipped) = parse_autojump(text, 1640995200);
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, "/home/user/projects");
+    assert_eq!(entries[0].rank, 1.0);
+    assert_eq!(entries[0].time, 1640995200);
+    // sqrt(10^2 * 10) ~ 31.6 is roughly ten visits
+    assert!((entries[1].rank - 10.0).abs() < 0.1);
+    assert_eq!(skipped.len(), 1);
+}
+
+#[test]
+fn test_parse_fasd() {
+    let text = "/home/user/projects|4|1640995200\n/home/user/a|b.txt|1|1640995100\nbroken\n";
+
+    let (entries, skipped) = parse_fasd(text);
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, "/home/user/projects");
+    assert_eq!(entries[0].rank, 8.0);
+    assert_eq!(entries[0].time, 1640995200);
+    // Pipes in paths are kept, and ranks are never normalized below 1
+    assert_eq!(entries[1].path, "/home/user/a|b.txt");
+    assert_eq!(entries[1].rank, 1.0);
+    assert_eq!(skipped, vec!["broken".to_string()]);
+}
+
+#[test]
+fn test_partition_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes.txt");
+    fs::write(&file, "").unwrap();
+    let dir_path = dir.path().to_string_lossy().to_string();
+    let file_path = file.to_string_lossy().to_string();
+
+    let (directories, skipped) = partition_directories(vec![
+        DirEntry::new(dir_path.clone(), 1.0, 0),
+        DirEntry::new(file_path.clone(), 1.0, 0),
+        DirEntry::new("/nonexistent/zjyo/path".to_string(), 1.0, 0),
+    ]);
+
+    assert_eq!(directories.len(), 1);
+    assert_eq!(directories[0].path, dir_path);
+    assert_eq!(
+        skipped,
+        vec![file_path, "/nonexistent/zjyo/path".to_string()]
+    );
+}
+
+fn export_to_string(entries: &[DirEntry], format: ExportFormat) -> String {
+    let mut out = Vec::new();
+    export(entries, format, 1640995200, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_export_formats_are_sorted_and_escaped() {
+    let entries = vec![
+        DirEntry::new("/srv/b,\"quoted\"".to_string(), 2.5, 1640995200),
+        DirEntry::new("/home/a\tb".to_string(), 4.0, 1640991600),
+    ];
+    let frecency_a = entries[1].frecency_at(1640995200);
+    let frecency_b = entries[0].frecency_at(1640995200);
+
+    assert_eq!(
+        export_to_string(&entries, ExportFormat::Json),
+        format!(
+            "[\n  {{\"path\": \"/home/a\\tb\", \"rank\": 4, \"time\": 1640991600, \"frecency\": {}}},\n  {{\"path\": \"/srv/b,\\\"quoted\\\"\", \"rank\": 2.5, \"time\": 1640995200, \"frecency\": {}}}\n]\n",
+            frecency_a, frecency_b
+        )
+    );
+    assert_eq!(
+        export_to_string(&entries, ExportFormat::Csv),
+        format!(
+            "path,rank,time,frecency\n/home/a\tb,4,1640991600,{}\n\"/srv/b,\"\"quoted\"\"\",2.5,1640995200,{}\n",
+            frecency_a, frecency_b
+        )
+    );
+    assert_eq!(
+        export_to_string(&entries, ExportFormat::Tsv),
+        format!(
+            "path\trank\ttime\tfrecency\n/home/a\\tb\t4\t1640991600\t{}\n/srv/b,\"quoted\"\t2.5\t1640995200\t{}\n",
+            frecency_a, frecency_b
+        )
+    );
+    assert_eq!(
+        export_to_string(&entries, ExportFormat::Zoxide),
+        "/home/a\tb|4|1640991600\n/srv/b,\"quoted\"|2.5|1640995200\n"
+    );
+}
+
+#[test]
+fn test_remove_takes_backup_and_undo_restores() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    db.entries.insert(
+        "/home/user/projects".to_string(),
+        DirEntry::new("/home/user/projects".to_string(), 5.0, 1640995200),
+    );
+    db.entries.insert(
+        "/mnt/usb/photos".to_string(),
+        DirEntry::new("/mnt/usb/photos".to_string(), 3.0, 1640995100),
+    );
+    db.save();
+
+    db.remove("/mnt/usb/photos");
+    assert!(db.backup_file(1).exists());
+    assert!(!db.entries.contains_key("/mnt/usb/photos"));
+
+    // Removing an unknown path is not destructive and takes no backup
+    db.remove("/not/tracked");
+    assert!(!db.backup_file(2).exists());
+
+    assert!(db.undo().unwrap());
+    assert!(db.entries.contains_key("/mnt/usb/photos"));
+    assert!(!db.backup_file(1).exists());
+    assert!(!db.undo().unwrap());
+}
+
+#[test]
+fn test_undo_restores_sidecars() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    db.add("/work/acme");
+    db.add("/work/other");
+    assert!(db.tag("/work/acme", &["acme".to_string()]));
+    assert!(db.set_weight("/work/acme", 3.0));
+    db.save();
+
+    db.remove("/work/acme");
+    assert!(!db.tags_file().exists());
+    assert!(!db.weights_file().exists());
+    assert!(db.sidecar_file("bak.1.tags").exists());
+
+    // The restored entry comes back with its tags, weight and history
+    assert!(db.undo().unwrap());
+    let acme = &db.entries["/work/acme"];
+    assert_eq!(acme.tags, vec!["acme".to_string()]);
+    assert_eq!(acme.weight, 3.0);
+    assert_eq!(acme.history.as_ref().unwrap().visits, 1);
+    assert_eq!(acme.visits.total(), 1);
+    assert!(!db.sidecar_file("bak.1.tags").exists());
+
+    // Sidecars that didn't exist when the backup was taken are removed
+    db.untag("/work/acme", &["acme".to_string()]);
+    db.save();
+    db.remove("/work/other");
+    assert!(db.tag("/work/acme", &["late".to_string()]));
+    db.save();
+    assert!(db.undo().unwrap());
+    assert!(db.entries.contains_key("/work/other"));
+    assert!(db.entries["/work/acme"].tags.is_empty());
+    assert!(!db.tags_file().exists());
+}
+
+#[test]
+fn test_snapshot_rotates_backups() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = ZDatabase::with_data_file(dir.path().join("z"));
+
+    for i in 0..7 {
+        fs::write(&db.data_file, format!("/path/{}|1|1640995200\n", i)).unwrap();
+        db.snapshot();
+    }
+
+    let backups = db.backups();
+    assert_eq!(backups.len(), 5);
+    assert_eq!(backups[0].index, 1);
+    assert_eq!(backups[0].entries, 1);
+    assert_eq!(
+        fs::read_to_string(db.backup_file(1)).unwrap(),
+        "/path/6|1|1640995200\n"
+    );
+    assert_eq!(
+        fs::read_to_string(db.backup_file(5)).unwrap(),
+        "/path/2|1|1640995200\n"
+    );
+}
+
+#[test]
+fn test_zoxide_scorer_buckets() {
+    let now = 1640995200;
+    let entry = |age: u64| DirEntry::new("/path".to_string(), 10.0, now - age);
+
+    assert_eq!(Zoxide.score(&entry(60), now), 40.0);
+    assert_eq!(Zoxide.score(&entry(2 * 3600), now), 20.0);
+    assert_eq!(Zoxide.score(&entry(2 * 86400), now), 5.0);
+    assert_eq!(Zoxide.score(&entry(30 * 86400), now), 2.5);
+}
+
+#[test]
+fn test_scorers_are_not_truncated() {
+    let now = 1640995200;
+    let entry = DirEntry::new("/path".to_string(), 1.5, now);
+
+    assert_eq!(Rank.score(&entry, now), 1.5);
+    assert_eq!(Frecency.score(&entry, now), entry.frecency_at(now));
+    assert_eq!(Frecency.score(&entry, now).fract(), 0.0);
+    assert!(
+        Frecency
+            .score(&DirEntry::new("/path".to_string(), 1.5, now - 7), now)
+            .fract()
+            > 0.0
+    );
+}
+
+#[test]
+fn test_find_with_scorer() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    // Visited often but over a week ago
+    db.entries.insert(
+        "/home/user/archive".to_string(),
+        DirEntry::new("/home/user/archive".to_string(), 20.0, now - 8 * 86400),
+    );
+
+    let by_rank = db.find_with("user", None, &Rank);
+    assert_eq!(by_rank[0].path, "/home/user/archive");
+
+    let by_zoxide = db.find_with("user", None, scorer_by_name("zoxide").unwrap().as_ref());
+    assert_eq!(by_zoxide[0].path, "/home/user/downloads");
+    assert_eq!(by_zoxide[3].path, "/home/user/archive");
+
+    assert!(scorer_by_name("unknown").is_none());
+}
+
+#[test]
+fn test_search_reports_exclusions_and_deciding_rule() {
+    let mut db = create_test_db();
+    db.entries.insert(
+        "/srv/user/projects".to_string(),
+        DirEntry::new("/srv/user/projects".to_string(), 9.0, 1640995200),
+    );
+
+    let query = Query {
+        current_dir: Some("/home/user"),
+        ..Query::new("user", &Rank)
+    };
+    let result = db.search(&query);
+
+    assert_eq!(result.candidates.len(), 3);
+    assert_eq!(result.candidates[0].entry.path, "/home/user/downloads");
+    assert_eq!(result.candidates[0].matched, vec!["user".to_string()]);
+    assert_eq!(result.excluded.len(), 1);
+    assert_eq!(result.excluded[0].entry.path, "/srv/user/projects");
+    assert!(result.excluded[0]
+        .reason
+        .contains("outside current directory"));
+
+    let rule = result.decided_by(&Rank).unwrap();
+    assert!(rule.contains("highest rank score"));
+    assert!(rule.contains("/home/user/projects"));
+}
+
+#[test]
+fn test_decided_by_tie_and_single_candidate() {
+    let mut db = create_test_db();
+    db.entries.insert(
+        "/home/user/desktop".to_string(),
+        DirEntry::new("/home/user/desktop".to_string(), 7.0, 1640995200),
+    );
+
+    let result = db.search(&Query::new("user", &Rank));
+    assert_eq!(result.candidates[0].entry.path, "/home/user/desktop");
+    assert!(result
+        .decided_by(&Rank)
+        .unwrap()
+        .contains("broken by path order"));
+
+    let result = db.search(&Query::new("documents", &Rank));
+    assert_eq!(result.decided_by(&Rank).unwrap(), "only matching candidate");
+
+    let result = db.search(&Query::new("nonexistent", &Rank));
+    assert!(result.decided_by(&Rank).is_none());
+}
+
+fn create_basename_test_db() -> ZDatabase {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.entries.insert(
+        "/home/me/api-notes/old/src".to_string(),
+        DirEntry::new("/home/me/api-notes/old/src".to_string(), 20.0, now),
+    );
+    db.entries.insert(
+        "/srv/projects/api".to_string(),
+        DirEntry::new("/srv/projects/api".to_string(), 8.0, now),
+    );
+    db.entries.insert(
+        "/srv/projects/api/docs".to_string(),
+        DirEntry::new("/srv/projects/api/docs".to_string(), 2.0, now),
+    );
+    db
+}
+
+#[test]
+fn test_match_mode_anywhere_prefers_frecency() {
+    let db = create_basename_test_db();
+
+    let result = db.search(&Query::new("api", &Frecency));
+
+    assert_eq!(result.candidates.len(), 3);
+    assert_eq!(
+        result.candidates[0].entry.path,
+        "/home/me/api-notes/old/src"
+    );
+}
+
+#[test]
+fn test_match_mode_basename_requires_last_component() {
+    let db = create_basename_test_db();
+    let query = |pattern| Query {
+        match_mode: MatchMode::Basename,
+        ..Query::new(pattern, &Frecency)
+    };
+
+    let result = db.search(&query("api"));
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(result.candidates[0].entry.path, "/srv/projects/api");
+
+    // Earlier words may match anywhere, only the last one must be in the basename
+    let result = db.search(&query("proj api"));
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(result.candidates[0].entry.path, "/srv/projects/api");
+
+    let result = db.search(&query("api doc"));
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(result.candidates[0].entry.path, "/srv/projects/api/docs");
+
+    let result = db.search(&query("api proj"));
+    assert!(result.candidates.is_empty());
+}
+
+#[test]
+fn test_match_mode_prefer_basename_boosts_last_component() {
+    let db = create_basename_test_db();
+    let query = |pattern| Query {
+        match_mode: MatchMode::PreferBasename,
+        ..Query::new(pattern, &Frecency)
+    };
+
+    let result = db.search(&query("api"));
+    assert_eq!(result.candidates.len(), 3);
+    assert_eq!(result.candidates[0].entry.path, "/srv/projects/api");
+    assert_eq!(result.candidates[0].adjustments.len(), 1);
+    assert!(result.candidates[1].adjustments.is_empty());
+    assert!(result
+        .decided_by(&Frecency)
+        .unwrap()
+        .contains("matches the last path component"));
+
+    // Multi-word patterns still require every word and boost on the last one
+    let result = db.search(&query("me src"));
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(
+        result.candidates[0].entry.path,
+        "/home/me/api-notes/old/src"
+    );
+    assert_eq!(result.candidates[0].adjustments.len(), 1);
+}
+
+#[test]
+fn test_fuzzy_quality() {
+    assert!(fuzzy::quality("prjrs", "/home/user/projects/rust", false).is_some());
+    assert!(fuzzy::quality("rsprj", "/home/user/projects/rust", false).is_none());
+    assert!(fuzzy::quality("", "/anything", false).is_some());
+
+    // A consecutive match starting at a component is perfect
+    assert_eq!(fuzzy::quality("api", "/srv/projects/api", false), Some(1.0));
+
+    // Word boundaries and consecutive characters beat scattered matches
+    let boundary = fuzzy::quality("pr", "/home/user/projects", false).unwrap();
+    let scattered = fuzzy::quality("pr", "/home/user/pump/or", false).unwrap();
+    assert!(boundary > scattered);
+    let camel = fuzzy::quality("wa", "/src/myWebApp", false).unwrap();
+    let inner = fuzzy::quality("wa", "/src/mywebapp", false).unwrap();
+    assert!(camel > inner);
+
+    // Case is ignored
+    assert!(fuzzy::quality("PRJ", "/home/user/projects", false).is_some());
+}
+
+#[test]
+fn test_fuzzy_search_combines_quality_with_frecency() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.entries.insert(
+        "/home/user/projects/rust".to_string(),
+        DirEntry::new("/home/user/projects/rust".to_string(), 5.0, now),
+    );
+    db.entries.insert(
+        "/home/user/pr/jars".to_string(),
+        DirEntry::new("/home/user/pr/jars".to_string(), 5.0, now),
+    );
+    let fuzzy_query = |pattern| Query {
+        fuzzy: true,
+        ..Query::new(pattern, &Frecency)
+    };
+
+    // Exact substring matching stays the default
+    assert!(db
+        .search(&Query::new("prjrs", &Frecency))
+        .candidates
+        .is_empty());
+
+    let result = db.search(&fuzzy_query("prjrs"));
+    assert_eq!(result.candidates.len(), 2);
+    assert_eq!(result.candidates[0].entry.path, "/home/user/pr/jars");
+    assert!(result.candidates[0].score <= result.candidates[0].base_score);
+
+    let result = db.search(&fuzzy_query("proj rust"));
+    assert_eq!(result.candidates[0].entry.path, "/home/user/projects/rust");
+    assert!(result.candidates[0].adjustments.is_empty());
+}
+
+#[test]
+fn test_edit_distances() {
+    assert_eq!(edit_distance("projects", "projects"), 0);
+    assert_eq!(edit_distance("projcts", "projects"), 1);
+    // Adjacent transpositions count as a single edit
+    assert_eq!(edit_distance("porj", "proj"), 1);
+    assert_eq!(edit_distance("", "abc"), 3);
+
+    assert_eq!(substring_distance("proj", "my-projects"), 0);
+    assert_eq!(substring_distance("porj", "my-projects"), 1);
+    assert_eq!(substring_distance("xyz", "projects"), 3);
+}
+
+#[test]
+fn test_suggest_corrects_path_components() {
+    let mut db = create_test_db();
+    db.entries.insert(
+        "/srv/rust/api".to_string(),
+        DirEntry::new("/srv/rust/api".to_string(), 3.0, 1640995200),
+    );
+
+    let suggestions = db.suggest(&Query::new("projcts", &Frecency));
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].pattern, "projects");
+    assert_eq!(suggestions[0].distance, 1);
+    assert_eq!(suggestions[0].target.path, "/home/user/projects");
+
+    // Only the misspelled word is corrected in multi-word patterns
+    let suggestions = db.suggest(&Query::new("rust apj", &Frecency));
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].pattern, "rust api");
+    assert_eq!(suggestions[0].target.path, "/srv/rust/api");
+
+    // Corrections must still lead to a match as a whole
+    assert!(!db
+        .suggest(&Query::new("documents apj", &Frecency))
+        .iter()
+        .any(|s| s.pattern == "documents api"));
+    assert!(db.suggest(&Query::new("zzzzzz", &Frecency)).is_empty());
+}
+
+#[test]
+fn test_suggest_whole_pattern() {
+    let mut db = create_test_db();
+    db.entries.insert(
+        "/home/user/my-project".to_string(),
+        DirEntry::new("/home/user/my-project".to_string(), 3.0, 1640995200),
+    );
+
+    let suggestions = db.suggest(&Query::new("myproj ecct", &Frecency));
+    assert!(suggestions.iter().any(|s| s.pattern == "my-project"));
+}
+
+#[test]
+fn test_abbreviation_matches() {
+    assert!(abbreviation_matches(
+        "p/r/api",
+        "/home/me/projects/rust/api"
+    ));
+    assert!(abbreviation_matches("p/r", "/home/me/projects/rust/api"));
+    assert!(abbreviation_matches("me/p/", "/home/me/projects/rust/api"));
+    // Components must be consecutive and matched by prefix
+    assert!(!abbreviation_matches("p/api", "/home/me/projects/rust/api"));
+    assert!(!abbreviation_matches("r/p", "/home/me/projects/rust/api"));
+    assert!(!abbreviation_matches(
+        "ojects/rust",
+        "/home/me/projects/rust/api"
+    ));
+    // A leading slash anchors at the root
+    assert!(abbreviation_matches("/h/m/p", "/home/me/projects"));
+    assert!(!abbreviation_matches("/m/p", "/home/me/projects"));
+}
+
+#[test]
+fn test_abbreviation_patterns_ordered_by_frecency() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.entries.insert(
+        "/home/me/projects/rust/api".to_string(),
+        DirEntry::new("/home/me/projects/rust/api".to_string(), 2.0, now),
+    );
+    db.entries.insert(
+        "/home/me/personal/recipes/apis".to_string(),
+        DirEntry::new("/home/me/personal/recipes/apis".to_string(), 6.0, now),
+    );
+    db.entries.insert(
+        "/home/me/projects/ruby/app".to_string(),
+        DirEntry::new("/home/me/projects/ruby/app".to_string(), 9.0, now),
+    );
+
+    let matches = db.find_matches("p/r/api", None);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].path, "/home/me/personal/recipes/apis");
+    assert_eq!(matches[1].path, "/home/me/projects/rust/api");
+
+    // Abbreviations combine with other words and the existing contains logic
+    let matches = db.find_matches("rust p/r/api", None);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].path, "/home/me/projects/rust/api");
+    let matches = db.find_matches("rust/api", None);
+    assert_eq!(matches.len(), 1);
+
+    // In basename mode the last abbreviated part must be in the last component
+    let query = Query {
+        match_mode: MatchMode::Basename,
+        ..Query::new("p/r", &Frecency)
+    };
+    assert!(db.search(&query).candidates.is_empty());
+}
+
+#[test]
+fn test_home_prefixed_patterns() {
+    let mut db = create_test_db();
+    let home = home::home_dir().unwrap().to_string_lossy().to_string();
+    let config = format!("{}/.config/nvim", home);
+    db.entries.insert(
+        config.clone(),
+        DirEntry::new(config.clone(), 1.0, 1640995200),
+    );
+    db.entries.insert(
+        "/etc/.config/nvim".to_string(),
+        DirEntry::new("/etc/.config/nvim".to_string(), 9.0, 1640995200),
+    );
+
+    let matches = db.find_matches("~/.config", None);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].path, config);
+
+    let matches = db.find_matches("~/.c/n", None);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].path, config);
+}
+
+#[test]
+fn test_parse_pattern_exclusions_and_escaping() {
+    let Pattern {
+        words, excluded, ..
+    } = parse_pattern("proj !archive -old \\!important \\-dash \\\\x ! -");
+
+    assert_eq!(words, vec!["proj", "!important", "-dash", "\\x", "!", "-"]);
+    assert_eq!(excluded, vec!["archive", "old"]);
+}
+
+#[test]
+fn test_exclusion_terms_in_every_query_mode() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.entries.insert(
+        "/home/user/archive/projects".to_string(),
+        DirEntry::new("/home/user/archive/projects".to_string(), 50.0, now),
+    );
+    db.entries.insert(
+        "/home/user/!important".to_string(),
+        DirEntry::new("/home/user/!important".to_string(), 1.0, now),
+    );
+
+    assert_eq!(
+        db.find_matches("proj", None)[0].path,
+        "/home/user/archive/projects"
+    );
+    for matches in [
+        db.find_matches("proj !archive", None),
+        db.find_matches("proj -ARCHIVE", None),
+        db.find_by_rank("proj !archive"),
+        db.find_by_time("proj !archive"),
+    ] {
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, "/home/user/projects");
+    }
+
+    // Exclusions alone filter the whole database
+    assert_eq!(db.find_matches("!user", None).len(), 0);
+
+    // Escaped words are matched literally
+    let matches = db.find_matches("\\!imp", None);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].path, "/home/user/!important");
+
+    let result = db.search(&Query::new("proj !archive", &Frecency));
+    assert_eq!(result.excluded.len(), 1);
+    assert_eq!(result.excluded[0].entry.path, "/home/user/archive/projects");
+    assert_eq!(
+        result.excluded[0].reason,
+        "contains excluded word \"archive\""
+    );
+}
+
+#[test]
+fn test_unicode_folding() {
+    // Full case folding and canonical equivalence
+    assert_eq!(fold("Straße", false), fold("STRASSE", false));
+    assert_eq!(fold("caf\u{e9}", false), fold("cafe\u{301}", false));
+    assert_ne!(fold("café", false), fold("cafe", false));
+    assert_eq!(fold("café", true), "cafe");
+    assert_eq!(fold("ΣΊΣΥΦΟΣ", false), fold("σίσυφος", false));
+
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    for path in ["/home/user/Straße", "/home/user/caf\u{e9}"] {
+        db.entries
+            .insert(path.to_string(), DirEntry::new(path.to_string(), 1.0, now));
+    }
+
+    assert_eq!(
+        db.find_matches("strasse", None)[0].path,
+        "/home/user/Straße"
+    );
+    assert_eq!(
+        db.find_matches("CAFE\u{301}", None)[0].path,
+        "/home/user/caf\u{e9}"
+    );
+    assert!(db.find_matches("cafe", None).is_empty());
+
+    let query = Query {
+        ignore_accents: true,
+        ..Query::new("cafe", &Frecency)
+    };
+    assert_eq!(db.search(&query).candidates.len(), 1);
+    let query = Query {
+        ignore_accents: true,
+        fuzzy: true,
+        ..Query::new("cfe", &Frecency)
+    };
+    assert_eq!(db.search(&query).candidates.len(), 1);
+}
+
+#[test]
+fn test_exclude_current_directory() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.entries.insert(
+        "/home/user/projects".to_string(),
+        DirEntry::new("/home/user/projects".to_string(), 50.0, now),
+    );
+    db.entries.insert(
+        "/home/user/old/projects".to_string(),
+        DirEntry::new("/home/user/old/projects".to_string(), 5.0, now),
+    );
+
+    let query = Query::new("proj", &Frecency);
+    assert_eq!(
+        db.search(&query).candidates[0].entry.path,
+        "/home/user/projects"
+    );
+
+    let query = Query {
+        exclude_dir: Some("/home/user/projects/"),
+        ..Query::new("proj", &Frecency)
+    };
+    let result = db.search(&query);
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(result.candidates[0].entry.path, "/home/user/old/projects");
+    assert_eq!(result.excluded[0].entry.path, "/home/user/projects");
+    assert_eq!(result.excluded[0].reason, "is the current directory");
+}
+
+#[test]
+fn test_repository_roots() {
+    let dir = tempfile::tempdir().unwrap();
+    let git_root = dir.path().join("api");
+    let hg_root = dir.path().join("web");
+    let deep = git_root.join("src").join("api");
+    fs::create_dir_all(git_root.join(".git")).unwrap();
+    fs::create_dir_all(hg_root.join(".hg")).unwrap();
+    fs::create_dir_all(&deep).unwrap();
+
+    assert_eq!(vcs::root_marker(&git_root), Some(".git"));
+    assert_eq!(vcs::root_marker(&hg_root), Some(".hg"));
+    assert_eq!(vcs::root_marker(&deep), None);
+
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let git_root = git_root.to_string_lossy().to_string();
+    let deep = deep.to_string_lossy().to_string();
+    db.entries
+        .insert(git_root.clone(), DirEntry::new(git_root.clone(), 10.0, now));
+    db.entries
+        .insert(deep.clone(), DirEntry::new(deep.clone(), 15.0, now));
+
+    assert_eq!(db.find_matches("api", None)[0].path, deep);
+
+    let query = Query {
+        repo_boost: Some(2.0),
+        ..Query::new("api", &Frecency)
+    };
+    let result = db.search(&query);
+    assert_eq!(result.candidates[0].entry.path, git_root);
+    assert_eq!(
+        result.candidates[0].adjustments[0].reason,
+        "repository root (.git)"
+    );
+
+    let query = Query {
+        repo_only: true,
+        ..Query::new("api", &Frecency)
+    };
+    let result = db.search(&query);
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(result.candidates[0].entry.path, git_root);
+    assert_eq!(result.excluded[0].entry.path, deep);
+    assert_eq!(result.excluded[0].reason, "not a repository root");
+}
+
+#[test]
+fn test_current_directory_excludes_siblings() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    for path in ["/work/app", "/work/app/src", "/work/app2/src"] {
+        db.entries
+            .insert(path.to_string(), DirEntry::new(path.to_string(), 1.0, now));
+    }
+
+    for current in ["/work/app", "/work/app/"] {
+        let matches = db.find_matches("src", Some(current));
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, "/work/app/src");
+    }
+    assert_eq!(db.find_matches("app", Some("/work/app")).len(), 2);
+    assert_eq!(db.find_matches("src", Some("/")).len(), 2);
+}
+
+#[test]
+fn test_scope_roots() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join("repo");
+    let package = repo.join("packages").join("web");
+    let cwd = package.join("src").join("components");
+    fs::create_dir_all(repo.join(".jj")).unwrap();
+    fs::create_dir_all(&cwd).unwrap();
+    fs::write(package.join("package.json"), "{}").unwrap();
+
+    let markers = vec!["package.json".to_string()];
+    assert_eq!(Scope::Cwd.root(&cwd, &markers), Some(cwd.as_path()));
+    assert_eq!(Scope::Repo.root(&cwd, &markers), Some(repo.as_path()));
+    assert_eq!(Scope::Marker.root(&cwd, &markers), Some(package.as_path()));
+    assert_eq!(Scope::Marker.root(&cwd, &["Cargo.toml".to_string()]), None);
+    assert_eq!("repo".parse::<Scope>(), Ok(Scope::Repo));
+    assert!("tree".parse::<Scope>().is_err());
+}
+
+#[test]
+fn test_proximity_to_current_directory() {
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.entries.insert(
+        "/work/other/docs".to_string(),
+        DirEntry::new("/work/other/docs".to_string(), 11.0, now),
+    );
+    db.entries.insert(
+        "/work/app/docs".to_string(),
+        DirEntry::new("/work/app/docs".to_string(), 10.0, now),
+    );
+
+    let query = Query {
+        near_dir: Some("/work/app/src"),
+        ..Query::new("docs", &Frecency)
+    };
+    assert_eq!(
+        db.search(&query).candidates[0].entry.path,
+        "/work/other/docs"
+    );
+
+    let query = Query {
+        near_dir: Some("/work/app/src"),
+        proximity_weight: 0.5,
+        ..Query::new("docs", &Frecency)
+    };
+    let result = db.search(&query);
+    let winner = &result.candidates[0];
+    assert_eq!(winner.entry.path, "/work/app/docs");
+    assert_eq!(
+        winner.adjustments[0].reason,
+        "shares 2 of 3 path components with /work/app/src"
+    );
+    assert!((winner.adjustments[0].factor - (1.0 + 0.5 * 2.0 / 3.0)).abs() < 1e-9);
+    assert!((result.candidates[1].adjustments[0].factor - (1.0 + 0.5 / 3.0)).abs() < 1e-9);
+}
+
+#[test]
+fn test_visit_histogram() {
+    let now = 1640995200;
+    let (hour, weekday) = local_slot(now);
+    let mut histogram = VisitHistogram::default();
+    assert_eq!(histogram.similarity(now), (0.0, 0.0));
+
+    // Two visits at this hour, one the hour after (counted half) and one
+    // half a day later on whichever weekday that falls in local time
+    let later = now + 12 * 3600;
+    histogram.record(now);
+    histogram.record(now);
+    histogram.record(now + 3600);
+    histogram.record(later);
+    assert_eq!(histogram.total(), 4);
+    assert_eq!(histogram.hours[hour], 2);
+
+    let same_day = [now + 3600, later]
+        .iter()
+        .filter(|time| local_slot(**time).1 == weekday)
+        .count();
+    let (hour_share, weekday_share) = histogram.similarity(now);
+    assert_eq!(hour_share, 2.5 / 4.0);
+    assert_eq!(weekday_share, (2 + same_day) as f64 / 4.0);
+
+    // Large histograms are halved instead of growing forever
+    let mut histogram = VisitHistogram::default();
+    for _ in 0..1001 {
+        histogram.record(now);
+    }
+    assert_eq!(histogram.total(), 501);
+}
+
+#[test]
+fn test_histogram_sidecar_and_routine_scorer() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    db.add("/home/user/work");
+    db.add("/home/user/work");
+    db.entries.insert(
+        "/home/user/hobby".to_string(),
+        DirEntry::new(
+            "/home/user/hobby".to_string(),
+            2.0,
+            db.entries["/home/user/work"].time,
+        ),
+    );
+    db.save();
+
+    // The datafile keeps the z format, histograms go to the sidecar
+    let data = fs::read_to_string(&db.data_file).unwrap();
+    assert!(data.lines().all(|line| line.split('|').count() == 3));
+    let sidecar = fs::read_to_string(db.visits_file()).unwrap();
+    assert_eq!(sidecar.lines().count(), 1);
+    assert!(sidecar.starts_with("/home/user/work|"));
+
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    let work = &loaded.entries["/home/user/work"];
+    let hobby = &loaded.entries["/home/user/hobby"];
+    assert_eq!(work.visits, db.entries["/home/user/work"].visits);
+    assert_eq!(work.visits.total(), 2);
+    assert!(hobby.visits.is_empty());
+
+    // Equal frecency, but work was visited at this time of day
+    let now = work.time;
+    assert_eq!(Frecency.score(work, now), Frecency.score(hobby, now));
+    assert_eq!(Routine.score(work, now), 3.0 * Frecency.score(work, now));
+    assert_eq!(Routine.score(hobby, now), Frecency.score(hobby, now));
+    assert!(Routine.explain(work, now).contains("1.00 of visits around"));
+    assert_eq!(scorer_by_name("routine").unwrap().name(), "routine");
+
+    // Old datafiles without a sidecar still load
+    fs::remove_file(db.visits_file()).unwrap();
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(loaded.entries.len(), 2);
+    assert!(loaded.entries["/home/user/work"].visits.is_empty());
+}
+
+#[test]
+fn test_visit_history() {
+    let mut history = VisitHistory::new(100);
+    for time in 101..=120 {
+        history.record(time);
+    }
+    assert_eq!(history.first_seen, Some(100));
+    assert_eq!(history.visits, 21);
+    assert_eq!(history.recent.len(), RECENT_VISITS);
+    assert_eq!(history.recent, (111..=120).collect::<Vec<u64>>());
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    db.add("/home/user/work");
+    db.add("/home/user/work");
+    db.entries.insert(
+        "/home/user/old".to_string(),
+        DirEntry::new("/home/user/old".to_string(), 9000.0, 1640995200),
+    );
+    db.add("/home/user/work");
+
+    // Aging scales rank, but not the true visit count
+    let work = &db.entries["/home/user/work"];
+    assert!(work.rank < 3.0);
+    let history = work.history.clone().unwrap();
+    assert_eq!(history.visits, 3);
+    assert_eq!(history.first_seen, Some(history.recent[0]));
+    assert!(db.entries["/home/user/old"].history.is_none());
+
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(loaded.entries["/home/user/work"].history, Some(history));
+    assert!(loaded.entries["/home/user/old"].history.is_none());
+
+    // Histories share the histogram sidecar, one line per visited entry
+    let sidecar = fs::read_to_string(db.visits_file()).unwrap();
+    assert_eq!(sidecar.lines().count(), 1);
+    let history = loaded.entries["/home/user/work"].history.as_ref().unwrap();
+    assert!(sidecar.trim_end().ends_with(&history.format()));
+    let data = fs::read_to_string(&db.data_file).unwrap();
+    assert!(data.lines().all(|line| line.split('|').count() == 3));
+}
+
+#[test]
+fn test_history_seeded_from_datafile() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    let last_visit = 1640995200;
+    db.entries.insert(
+        "/home/user/veteran".to_string(),
+        DirEntry::new("/home/user/veteran".to_string(), 500.0, last_visit),
+    );
+    db.add("/home/user/newcomer");
+    db.add("/home/user/newcomer");
+    db.add("/home/user/veteran");
+
+    // A directory tracked for years doesn't restart as a first visit
+    let history = db.entries["/home/user/veteran"].history.clone().unwrap();
+    assert_eq!(history.first_seen, None);
+    assert_eq!(history.visits, 501);
+    assert_eq!(history.recent[0], last_visit);
+    assert_eq!(history.recent.len(), 2);
+
+    let now = db.entries["/home/user/veteran"].time;
+    assert!(
+        History.score(&db.entries["/home/user/veteran"], now)
+            > History.score(&db.entries["/home/user/newcomer"], now)
+    );
+
+    // The unknown first visit survives a round trip through the sidecar
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(loaded.entries["/home/user/veteran"].history, Some(history));
+}
+
+#[test]
+fn test_history_scorer() {
+    let now = 1640995200;
+    let mut entry = DirEntry::new("/path".to_string(), 2.0, now - 60);
+    assert_eq!(History.score(&entry, now), Zoxide.score(&entry, now));
+    assert!(History.explain(&entry, now).starts_with("no visit history"));
+
+    // Two visits within the hour (x4) and two over a week ago (x0.25)
+    let mut history = VisitHistory::new(now - 30 * 86400);
+    history.record(now - 20 * 86400);
+    history.record(now - 600);
+    history.record(now - 60);
+    entry.history = Some(history);
+    assert_eq!(History.score(&entry, now), 4.0 * 2.125);
+    assert_eq!(
+        History.explain(&entry, now),
+        "4 visits * mean weight 2.12 of the last 4 visits = 8.50"
+    );
+}
+
+#[test]
+fn test_pins() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = ZDatabase::with_data_file(dir.path().join("z"));
+    assert_eq!(db.load_pins(), Pins::default());
+
+    let mut pins = Pins::default();
+    assert_eq!(pins.pin("prod", "/srv/config"), Ok(None));
+    assert_eq!(pins.pin("cfg", "/srv/config"), Ok(None));
+    assert_eq!(pins.pin("oncall", "/srv/runbook"), Ok(None));
+    assert_eq!(
+        pins.pin("oncall", "/srv/oncall"),
+        Ok(Some("/srv/runbook".to_string()))
+    );
+    for alias in ["", "two words", "-x", "!prod", "a|b"] {
+        assert!(pins.pin(alias, "/srv").is_err());
+    }
+
+    assert_eq!(pins.resolve("prod"), Some("/srv/config"));
+    assert_eq!(pins.resolve(" prod "), Some("/srv/config"));
+    assert_eq!(pins.resolve("pro"), None);
+    assert_eq!(pins.resolve("prod api"), None);
+    assert_eq!(pins.aliases_for("/srv/config"), vec!["cfg", "prod"]);
+
+    db.save_pins(&pins).unwrap();
+    assert_eq!(
+        fs::read_to_string(db.pins_file()).unwrap(),
+        "cfg|/srv/config\noncall|/srv/oncall\nprod|/srv/config\n"
+    );
+    assert_eq!(db.load_pins(), pins);
+
+    pins.unpin("prod");
+    pins.unpin("cfg");
+    assert_eq!(pins.unpin("oncall"), Some("/srv/oncall".to_string()));
+    assert_eq!(pins.unpin("oncall"), None);
+    db.save_pins(&pins).unwrap();
+    assert!(!db.pins_file().exists());
+}
+
+#[test]
+fn test_parse_pattern_tags() {
+    let pattern = parse_pattern("@acme api !@archived -@old \\@home @ !@");
+    assert_eq!(pattern.words, vec!["api", "@home", "@"]);
+    assert_eq!(pattern.tags, vec!["acme"]);
+    assert_eq!(pattern.excluded_tags, vec!["archived", "old"]);
+    assert_eq!(pattern.excluded, vec!["@"]);
+
+    assert_eq!(parse_tag("@acme"), Ok("acme".to_string()));
+    assert_eq!(parse_tag("acme"), Ok("acme".to_string()));
+    for tag in ["@", "a b", "a,b", "a|b", "-x", "!x"] {
+        assert!(parse_tag(tag).is_err());
+    }
+}
+
+#[test]
+fn test_tag_filtered_queries() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    for (path, rank) in [
+        ("/work/acme/api", 1.0),
+        ("/work/globex/api", 50.0),
+        ("/work/acme/web", 2.0),
+    ] {
+        db.entries
+            .insert(path.to_string(), DirEntry::new(path.to_string(), rank, now));
+    }
+    let tags = |names: &[&str]| -> Vec<String> { names.iter().map(|t| t.to_string()).collect() };
+    assert!(db.tag("/work/acme/api", &tags(&["acme", "Client"])));
+    assert!(db.tag("/work/acme/web", &tags(&["acme"])));
+    assert!(!db.tag("/work/unknown", &tags(&["acme"])));
+    assert_eq!(db.entries["/work/acme/api"].tags, tags(&["Client", "acme"]));
+
+    assert_eq!(db.find_matches("api", None)[0].path, "/work/globex/api");
+    for matches in [
+        db.find_matches("@acme api", None),
+        db.find_matches("api @ACME @client", None),
+        db.find_by_rank("@acme api"),
+        db.find_by_time("@acme api"),
+    ] {
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, "/work/acme/api");
+    }
+    assert_eq!(db.find_matches("@acme", None).len(), 2);
+    assert_eq!(db.find_matches("@nobody", None).len(), 0);
+
+    let result = db.search(&Query::new("api !@acme", &Frecency));
+    assert_eq!(result.candidates.len(), 1);
+    assert_eq!(result.excluded[0].entry.path, "/work/acme/api");
+    assert_eq!(result.excluded[0].reason, "tagged @acme");
+
+    // Suggestions keep the tags of the pattern
+    let suggestions = db.suggest(&Query::new("@acme wbe", &Frecency));
+    assert_eq!(suggestions[0].pattern, "web @acme");
+    assert_eq!(suggestions[0].target.path, "/work/acme/web");
+
+    // Tags persist in a sidecar and survive a reload
+    db.save();
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(
+        loaded.entries["/work/acme/api"].tags,
+        tags(&["Client", "acme"])
+    );
+    assert_eq!(
+        loaded.untag("/work/acme/api", &tags(&["acme", "other"])),
+        tags(&["acme"])
+    );
+    assert_eq!(loaded.entries["/work/acme/api"].tags, tags(&["Client"]));
+}
+
+#[test]
+fn test_manual_weights() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    for (path, rank) in [
+        ("/work/app/node_modules/pkg", 40.0),
+        ("/work/pkg", 10.0),
+        ("/work/key/pkg", 1.0),
+        ("/work/vendor/lib", 1.0),
+        ("/work/old", 8950.0),
+    ] {
+        db.entries
+            .insert(path.to_string(), DirEntry::new(path.to_string(), rank, now));
+    }
+    assert_eq!(parse_weight("0.1"), Ok(0.1));
+    for value in ["0", "-2", "inf", "NaN", "x"] {
+        assert!(parse_weight(value).is_err());
+    }
+    assert!(db.set_weight("/work/app/node_modules/pkg", 0.1));
+    assert!(db.set_weight("/work/key/pkg", 30.0));
+    assert!(db.set_weight("/work/vendor/lib", 0.1));
+    assert!(!db.set_weight("/work/unknown", 2.0));
+
+    // The weight applies whatever the scorer
+    for scorer in [&Frecency as &dyn Scorer, &Rank, &Zoxide, &History] {
+        let result = db.search(&Query::new("pkg", scorer));
+        let paths: Vec<&str> = result
+            .candidates
+            .iter()
+            .map(|c| c.entry.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["/work/key/pkg", "/work/pkg", "/work/app/node_modules/pkg"],
+            "{}",
+            scorer.name()
+        );
+        assert_eq!(result.candidates[0].adjustments[0].reason, "manual weight");
+    }
+
+    // Aging decays the rank but keeps weights, and weighted entries, so
+    // demoted ones don't return at full weight
+    db.add("/work/pkg");
+    assert!(db.entries["/work/key/pkg"].rank < 1.0);
+    assert_eq!(db.entries["/work/key/pkg"].weight, 30.0);
+    assert!(db.entries["/work/vendor/lib"].rank < 1.0);
+    assert_eq!(db.entries["/work/vendor/lib"].weight, 0.1);
+    assert_eq!(db.entries["/work/app/node_modules/pkg"].weight, 0.1);
+
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(loaded.entries["/work/key/pkg"].weight, 30.0);
+    assert_eq!(loaded.entries["/work/app/node_modules/pkg"].weight, 0.1);
+    assert_eq!(loaded.entries["/work/pkg"].weight, 1.0);
+
+    loaded.set_weight("/work/key/pkg", 1.0);
+    loaded.set_weight("/work/app/node_modules/pkg", 1.0);
+    loaded.set_weight("/work/vendor/lib", 1.0);
+    loaded.save();
+    assert!(!loaded.weights_file().exists());
+}
+
+#[test]
+fn test_learned_selections() {
+    let mut learned = Learned::default();
+    learned.record("Web ", "/srv/frontend", 100);
+    learned.record("web", "/srv/frontend", 200);
+    learned.record("web app", "/srv/frontend", 300);
+    learned.record("we", "/srv/web-legacy", 400);
+    learned.record("a|b", "/srv/pipe", 500);
+    learned.record("  ", "/srv/blank", 600);
+    assert_eq!(learned.selections.len(), 3);
+    assert_eq!(learned.selections[0].count, 2);
+    assert_eq!(learned.selections[0].time, 200);
+
+    assert_eq!(learned.selections_for("web", "/srv/frontend"), (2, 1));
+    assert_eq!(learned.selections_for("w", "/srv/frontend"), (0, 3));
+    assert_eq!(learned.selections_for("web app", "/srv/frontend"), (1, 0));
+    assert_eq!(learned.selections_for("frontend", "/srv/frontend"), (0, 0));
+    assert_eq!(learned.selections_for("web", "/srv/web-legacy"), (0, 0));
+
+    let (factor, reason) = learned.boost("web", "/srv/frontend").unwrap();
+    assert_eq!(factor, 1.0 + 2.5f64.ln_1p());
+    assert_eq!(
+        reason,
+        "chosen 2 times for \"web\" and once for longer queries"
+    );
+    assert!(learned.boost("api", "/srv/frontend").is_none());
+
+    // Learned selections rank otherwise weaker matches first
+    let mut db = create_test_db();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    for (path, rank) in [("/srv/frontend/web", 12.0), ("/srv/web-legacy", 20.0)] {
+        db.entries
+            .insert(path.to_string(), DirEntry::new(path.to_string(), rank, now));
+    }
+    let mut learned = Learned::default();
+    for _ in 0..3 {
+        learned.record("web", "/srv/frontend/web", now);
+    }
+    assert_eq!(db.find_matches("web", None)[0].path, "/srv/web-legacy");
+    for pattern in ["web", "we"] {
+        let query = Query {
+            learned: Some(&learned),
+            ..Query::new(pattern, &Frecency)
+        };
+        assert_eq!(
+            db.search(&query).candidates[0].entry.path,
+            "/srv/frontend/web"
+        );
+    }
+
+    // Storage is bounded, forgetting the least recently chosen pairs
+    let mut learned = Learned::default();
+    for i in 0..MAX_SELECTIONS as u64 + 10 {
+        learned.record(&format!("q{}", i), "/srv", i);
+    }
+    assert_eq!(learned.selections.len(), MAX_SELECTIONS);
+    assert!(learned.selections.iter().all(|s| s.time >= 10));
+
+    let dir = tempfile::tempdir().unwrap();
+    db.data_file = dir.path().join("z");
+    db.save_learned(&learned).unwrap();
+    assert_eq!(db.load_learned(), learned);
+    db.save_learned(&Learned::default()).unwrap();
+    assert!(!db.learned_file().exists());
+}
+
+#[test]
+fn test_dwell_weighted_ranks() {
+    assert_eq!(dwell_increment(2, 5), None);
+    assert_eq!(dwell_increment(5, 5), Some(MIN_INCREMENT));
+    assert_eq!(dwell_increment(DWELL_UNIT, 5), Some(1.0));
+    assert_eq!(dwell_increment(DWELL_UNIT * 3 / 2, 5), Some(1.5));
+    assert_eq!(dwell_increment(8 * 3600, 5), Some(MAX_INCREMENT));
+    assert_eq!(dwell_increment(0, 0), Some(MIN_INCREMENT));
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+
+    // Passing through doesn't create or bump entries
+    assert_eq!(db.leave("/tmp/passing", 1, 5), None);
+    assert!(db.entries.is_empty());
+
+    assert_eq!(db.leave("/work/project", 600, 5), Some(2.0));
+    assert_eq!(db.leave("/work/project", 3 * 3600, 5), Some(MAX_INCREMENT));
+    let entry = &db.entries["/work/project"];
+    assert_eq!(entry.rank, 6.0);
+    assert_eq!(entry.history.as_ref().unwrap().visits, 2);
+
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(loaded.entries["/work/project"].rank, 6.0);
+    // The visit is dated to the arrival, not the departure
+    let now = 1_700_000_000;
+    db.leave_at("/work/late", 600, 5, Aging::Threshold, &FixedClock(now));
+    let entry = &db.entries["/work/late"];
+    assert_eq!(entry.time, now - 600);
+    assert_eq!(entry.history.as_ref().unwrap().recent, vec![now - 600]);
+    let mut histogram = VisitHistogram::default();
+    histogram.record(now - 600);
+    assert_eq!(entry.visits, histogram);
+}
+
+#[test]
+fn test_half_life_decay() {
+    const DAY: u64 = 24 * 60 * 60;
+    assert_eq!(parse_half_life("7"), Ok(Aging::HalfLife(7.0 * DAY as f64)));
+    for value in ["0", "-1", "inf", "week"] {
+        assert!(parse_half_life(value).is_err());
+    }
+    assert_eq!(decay_factor(0, DAY as f64), 1.0);
+    assert_eq!(decay_factor(2 * DAY, DAY as f64), 0.25);
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut db = ZDatabase::with_data_file(dir.path().join("z"));
+    let start = 1_700_000_000;
+    for (path, rank) in [("/work/old", 8.0), ("/work/stale", 0.3)] {
+        db.entries.insert(
+            path.to_string(),
+            DirEntry::new(path.to_string(), rank, start),
+        );
+    }
+    db.set_weight("/work/stale", 2.0);
+    let aging = Aging::HalfLife(DAY as f64);
+
+    // The first visit only starts the clock, even for small databases
+    db.add_at("/work/new", 1.0, aging, &FixedClock(start));
+    assert_eq!(db.entries["/work/old"].rank, 8.0);
+    assert_eq!(db.decayed_at, Some(start));
+    assert_eq!(
+        fs::read_to_string(db.decay_file()).unwrap(),
+        format!("{}\n", start)
+    );
+
+    // Elapsed time is applied before the visit is counted
+    db.add_at("/work/new", 1.0, aging, &FixedClock(start + DAY));
+    assert_eq!(db.entries["/work/old"].rank, 4.0);
+    assert_eq!(db.entries["/work/new"].rank, 1.5);
+
+    // Entries falling below the floor are dropped unless weighted
+    let aged_out = db.age_with(aging, &FixedClock(start + 5 * DAY));
+    assert_eq!(aged_out.len(), 1);
+    assert_eq!(aged_out[0].path, "/work/new");
+    assert_eq!(db.entries["/work/old"].rank, 0.25);
+    assert!(db.entries["/work/stale"].rank < DECAY_FLOOR);
+
+    // Threshold aging leaves small databases alone
+    db.add_at(
+        "/work/old",
+        1.0,
+        Aging::Threshold,
+        &FixedClock(start + 9 * DAY),
+    );
+    assert_eq!(db.entries["/work/old"].rank, 1.25);
+
+    // Merged entries arrive after the decay, and as nothing is saved, a dry
+    // run leaves the decay time with the saved ranks
+    let mut loaded = ZDatabase::with_data_file(db.data_file.clone());
+    loaded.load();
+    assert_eq!(loaded.decayed_at, Some(start + 5 * DAY));
+    let report = loaded.merge_at(
+        vec![DirEntry::new("/work/archive".to_string(), 3.0, start)],
+        MergePolicy::Sum,
+        aging,
+        &FixedClock(start + 20 * DAY),
+    );
+    let aged_out: Vec<&str> = report.aged_out.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(aged_out, vec!["/work/old"]);
+    assert_eq!(loaded.entries["/work/archive"].rank, 3.0);
+    assert_eq!(loaded.decayed_at, Some(start + 20 * DAY));
+    loaded.load_decay();
+    assert_eq!(loaded.decayed_at, Some(start + 5 * DAY));
+}
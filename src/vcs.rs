@@ -0,0 +1,99 @@
+use std::env;
+use std::path::Path;
+use std::str::FromStr;
+
+// Entries that mark the root of a git, Mercurial or Jujutsu repository.
+pub const VCS_MARKERS: &[&str] = &[".git", ".hg", ".jj"];
+
+pub const SCOPES: &[&str] = &["cwd", "repo", "marker"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    // Subdirectories of the current directory (-c)
+    Cwd,
+    // The enclosing repository root and everything below it
+    Repo,
+    // The nearest ancestor containing one of the configured marker files
+    Marker,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cwd" => Ok(Scope::Cwd),
+            "repo" => Ok(Scope::Repo),
+            "marker" => Ok(Scope::Marker),
+            _ => Err(format!("unknown scope: {}", s)),
+        }
+    }
+}
+
+impl Scope {
+    // The directory that matches are restricted to when working in `cwd`.
+    pub fn root<'a>(&self, cwd: &'a Path, markers: &[String]) -> Option<&'a Path> {
+        match self {
+            Scope::Cwd => Some(cwd),
+            Scope::Repo => cwd.ancestors().find(|dir| root_marker(dir).is_some()),
+            Scope::Marker => cwd
+                .ancestors()
+                .find(|dir| markers.iter().any(|marker| dir.join(marker).exists())),
+        }
+    }
+}
+
+// Boost for repository roots when enabled without an explicit factor.
+pub const DEFAULT_REPO_BOOST: f64 = 2.0;
+
+// The marker that makes `dir` a repository root, if any. `.git` may be a
+// file for worktrees and submodules, so any kind of entry counts.
+pub fn root_marker(dir: &Path) -> Option<&'static str> {
+    VCS_MARKERS
+        .iter()
+        .copied()
+        .find(|marker| dir.join(marker).exists())
+}
+
+// Parses a repository root boost, which must be a positive finite factor.
+pub fn parse_repo_boost(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(factor),
+        _ => Err(format!(
+            "invalid boost: {} (expected a factor above 0)",
+            value
+        )),
+    }
+}
+
+// The repository root boost configured through `_Z_REPO_BOOST`, either `1`
+// for the default factor or the factor itself.
+pub fn default_repo_boost() -> Option<f64> {
+    let value = env::var("_Z_REPO_BOOST").ok()?;
+    match value.as_str() {
+        "" | "0" => None,
+        "1" => Some(DEFAULT_REPO_BOOST),
+        _ => match parse_repo_boost(&value) {
+            Ok(factor) => Some(factor),
+            Err(err) => {
+                eprintln!("z: {} in _Z_REPO_BOOST", err);
+                None
+            }
+        },
+    }
+}
+
+// Marker files for the marker scope configured through `_Z_SCOPE_MARKERS`
+// as a comma-separated list, such as `package.json,Cargo.toml`.
+pub fn scope_markers() -> Vec<String> {
+    env::var("_Z_SCOPE_MARKERS")
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|marker| !marker.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
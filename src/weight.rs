@@ -0,0 +1,60 @@
+use crate::database::ZDatabase;
+use crate::sidecar;
+use std::io;
+use std::path::PathBuf;
+
+// Parses a manual weight, which must be a positive finite number.
+pub fn parse_weight(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight > 0.0 => Ok(weight),
+        _ => Err(format!(
+            "invalid weight: {} (expected a number above 0)",
+            value
+        )),
+    }
+}
+
+impl ZDatabase {
+    pub fn weights_file(&self) -> PathBuf {
+        self.sidecar_file("weights")
+    }
+
+    // Attaches the manual weights in the sidecar to loaded entries.
+    pub fn load_weights(&mut self) {
+        for line in sidecar::read_lines(&self.weights_file()) {
+            if let Some((path, weight)) = line.rsplit_once('|') {
+                if let (Some(entry), Ok(weight)) =
+                    (self.entries.get_mut(path), parse_weight(weight))
+                {
+                    entry.weight = weight;
+                }
+            }
+        }
+    }
+
+    pub fn save_weights(&self) -> io::Result<()> {
+        let mut entries: Vec<_> = self
+            .entries
+            .values()
+            .filter(|entry| entry.weight != 1.0)
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let lines: Vec<String> = entries
+            .into_iter()
+            .map(|entry| format!("{}|{}", entry.path, entry.weight))
+            .collect();
+        sidecar::write_lines(&self.weights_file(), &lines)
+    }
+
+    // Sets the manual weight of a tracked directory, returning false when it
+    // isn't one. A weight of 1 clears it.
+    pub fn set_weight(&mut self, path: &str, weight: f64) -> bool {
+        match self.entries.get_mut(path) {
+            Some(entry) => {
+                entry.weight = weight;
+                true
+            }
+            None => false,
+        }
+    }
+}
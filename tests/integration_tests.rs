@@ -1,7 +1,7 @@
 use std::env;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn get_binary_path() -> PathBuf {
@@ -37,6 +37,20 @@ fn get_binary_path() -> PathBuf {
     path
 }
 
+// A zjyo command run in `dir` with the datafile `dir/z`.
+fn zjyo_command(dir: &Path) -> Command {
+    let mut command = Command::new(get_binary_path());
+    command.current_dir(dir).env("_Z_DATA", dir.join("z"));
+    command
+}
+
+fn zjyo(dir: &Path, args: &[&str]) -> Output {
+    zjyo_command(dir)
+        .args(args)
+        .output()
+        .expect("Failed to execute command")
+}
+
 fn create_temp_data_file() -> String {
     format!(
         "/tmp/test_zjyo_integration_{}",
@@ -288,3 +302,1075 @@ fn test_empty_database() {
     let list_output = String::from_utf8(output.stdout).unwrap();
     assert!(list_output.trim().is_empty());
 }
+
+#[test]
+fn test_merge_dry_run() {
+    let temp_data = create_temp_data_file();
+    let other_data = format!("{}_other", temp_data);
+
+    fs::write(&temp_data, "/path/shared|2.0|1640995200\n").expect("Failed to write test data");
+    fs::write(
+        &other_data,
+        "/path/shared|3.0|1640995300\n/path/laptop|1.0|1640995100\n",
+    )
+    .expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("merge")
+        .arg("--dry-run")
+        .arg(&other_data)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("would add"));
+    assert!(report.contains("/path/laptop"));
+    assert!(report.contains("would update"));
+
+    // Dry run must leave the datafile untouched
+    let contents = fs::read_to_string(&temp_data).unwrap();
+    assert_eq!(contents, "/path/shared|2.0|1640995200\n");
+
+    let output = Command::new(get_binary_path())
+        .arg("merge")
+        .arg(&other_data)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let contents = fs::read_to_string(&temp_data).unwrap();
+    assert!(contents.contains("/path/shared|5|1640995300"));
+    assert!(contents.contains("/path/laptop|1|1640995100"));
+
+    // --weight only applies to the weighted policy and must be above 0
+    for args in [
+        vec!["--weight", "0.5"],
+        vec!["--policy", "weighted", "--weight=-1"],
+        vec!["--policy", "weighted", "--weight", "0"],
+    ] {
+        let output = Command::new(get_binary_path())
+            .arg("merge")
+            .args(&args)
+            .arg(&other_data)
+            .env("_Z_DATA", &temp_data)
+            .output()
+            .expect("Failed to execute command");
+        assert!(!output.status.success(), "{:?}", args);
+    }
+    let contents = fs::read_to_string(&temp_data).unwrap();
+    assert!(contents.contains("/path/shared|5|1640995300"));
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+    fs::remove_file(&other_data).ok();
+}
+
+#[test]
+fn test_import_zoxide_query_output() {
+    let temp_data = create_temp_data_file();
+    let query_output = format!("{}_zoxide", temp_data);
+
+    fs::write(&temp_data, "/home/user/projects|5.0|1640995200\n")
+        .expect("Failed to write test data");
+    fs::write(
+        &query_output,
+        "  20.0 /home/user/projects\n   8.0 /srv/zoxide-only\n",
+    )
+    .expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("import")
+        .arg("zoxide")
+        .arg(&query_output)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("1 imported, 1 merged, 0 skipped"));
+
+    let contents = fs::read_to_string(&temp_data).unwrap();
+    assert!(contents.contains("/home/user/projects|10|"));
+    assert!(contents.contains("/srv/zoxide-only|2|"));
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+    fs::remove_file(&query_output).ok();
+}
+
+#[test]
+fn test_import_fasd_skips_files() {
+    let temp_data = create_temp_data_file();
+    let fasd_dir = tempfile::tempdir().unwrap();
+    let tracked_dir = fasd_dir.path().join("project");
+    let tracked_file = fasd_dir.path().join("notes.txt");
+    fs::create_dir(&tracked_dir).unwrap();
+    fs::write(&tracked_file, "").unwrap();
+
+    let fasd_data = fasd_dir.path().join(".fasd");
+    fs::write(
+        &fasd_data,
+        format!(
+            "{}|4|1640995200\n{}|2|1640995100\n",
+            tracked_dir.display(),
+            tracked_file.display()
+        ),
+    )
+    .expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("import")
+        .arg("fasd")
+        .arg(&fasd_data)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("1 imported, 0 merged, 1 skipped"));
+
+    let contents = fs::read_to_string(&temp_data).unwrap();
+    assert!(contents.contains(&format!("{}|8|1640995200", tracked_dir.display())));
+    assert!(!contents.contains("notes.txt"));
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_export_csv() {
+    let temp_data = create_temp_data_file();
+
+    let test_db_content = "/path/zeta|2.0|1640995200\n/path/alpha|5.0|1640995100\n";
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("export")
+        .arg("--format")
+        .arg("csv")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+
+    // Sorted by path, independent of datafile order
+    assert_eq!(lines[0], "path,rank,time,frecency");
+    assert!(lines[1].starts_with("/path/alpha,5,1640995100,"));
+    assert!(lines[2].starts_with("/path/zeta,2,1640995200,"));
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_undo_restores_removed_directory() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+
+    let current_dir = env::current_dir().unwrap().to_string_lossy().to_string();
+    let test_db_content = format!(
+        "{}|5.0|1640995200\n/tmp/to_keep|3.0|1640995100\n",
+        current_dir
+    );
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-x")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("backups")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let backups = String::from_utf8(output.stdout).unwrap();
+    assert!(backups.contains("2 entries"));
+
+    let output = Command::new(get_binary_path())
+        .arg("undo")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let contents = fs::read_to_string(&temp_data).unwrap();
+    assert!(contents.contains(&current_dir));
+    assert!(contents.contains("/tmp/to_keep"));
+}
+
+#[test]
+fn test_scorer_selection() {
+    let temp_data = create_temp_data_file();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let test_db_content = format!(
+        "/path/frequent|50.0|{}\n/path/recent|2.0|{}\n",
+        now - 30 * 86400,
+        now - 60
+    );
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--scorer")
+        .arg("rank")
+        .arg("path")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/path/frequent"
+    );
+
+    // The scorer can also be configured through the environment
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("path")
+        .env("_Z_DATA", &temp_data)
+        .env("_Z_SCORER", "rank")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/path/frequent"
+    );
+
+    // The command line takes precedence over the environment
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--scorer")
+        .arg("frecency")
+        .arg("path")
+        .env("_Z_DATA", &temp_data)
+        .env("_Z_SCORER", "rank")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/path/recent"
+    );
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_explain_output() {
+    let temp_data = create_temp_data_file();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let test_db_content = format!(
+        "/home/user/projects|5.0|{}\n/home/user/old-projects|2.0|{}\n",
+        now - 60,
+        now - 86400
+    );
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("--explain")
+        .arg("proj")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert!(explanation.contains("1. /home/user/projects  [winner]"));
+    assert!(explanation.contains("rank 5, last visit 1m ago"));
+    assert!(explanation.contains("frecency: 10000 * rank 5 * 3.75"));
+    assert!(explanation.contains("matched: proj"));
+    assert!(explanation.contains("2. /home/user/old-projects"));
+    assert!(explanation.contains("winner: /home/user/projects"));
+    assert!(explanation.contains("decided by: highest frecency score"));
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_basename_match_mode() {
+    let temp_data = create_temp_data_file();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let test_db_content = format!(
+        "/home/me/api-notes/old/src|20.0|{}\n/srv/projects/api|8.0|{}\n",
+        now, now
+    );
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("api")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/home/me/api-notes/old/src"
+    );
+
+    for mode in ["basename", "prefer-basename"] {
+        let output = Command::new(get_binary_path())
+            .arg("-e")
+            .arg("api")
+            .env("_Z_DATA", &temp_data)
+            .env("_Z_MATCH", mode)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            "/srv/projects/api"
+        );
+    }
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_fuzzy_matching() {
+    let temp_data = create_temp_data_file();
+
+    let test_db_content =
+        "/home/user/projects/rust|5.0|1640995200\n/home/user/documents|9.0|1640995200\n";
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("prjrs")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--fuzzy")
+        .arg("prjrs")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/home/user/projects/rust"
+    );
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_no_match_suggestions_and_autocorrect() {
+    let temp_data = create_temp_data_file();
+
+    let test_db_content = "/home/user/projects|5.0|1640995200\n";
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("porjects")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("no matches found for: porjects"));
+    assert!(stderr.contains("did you mean: projects"));
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("porjects")
+        .env("_Z_DATA", &temp_data)
+        .env("_Z_AUTOCORRECT", "1")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/home/user/projects"
+    );
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_exclusion_terms() {
+    let temp_data = create_temp_data_file();
+
+    let test_db_content =
+        "/home/user/archive/projects|50.0|1640995200\n/home/user/projects|5.0|1640995200\n";
+    fs::write(&temp_data, test_db_content).expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("proj !archive")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/home/user/projects"
+    );
+
+    // A pattern starting with an exclusion needs -- so it isn't read as options
+    let output = Command::new(get_binary_path())
+        .args(["-e", "--", "-archive proj"])
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/home/user/projects"
+    );
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_ignore_accents() {
+    let temp_data = create_temp_data_file();
+
+    fs::write(&temp_data, "/home/user/Café|5.0|1640995200\n").expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("CAFÉ")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("cafe")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--ignore-accents")
+        .arg("cafe")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/home/user/Café"
+    );
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_jump_skips_current_directory() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let projects = temp_dir.path().join("projects");
+    let old_projects = temp_dir.path().join("old").join("projects");
+    fs::create_dir_all(&projects).unwrap();
+    fs::create_dir_all(&old_projects).unwrap();
+    let projects = projects.canonicalize().unwrap();
+    let old_projects = old_projects.canonicalize().unwrap();
+    fs::write(
+        &temp_data,
+        format!(
+            "{}|50.0|1640995200\n{}|5.0|1640995200\n",
+            projects.display(),
+            old_projects.display()
+        ),
+    )
+    .expect("Failed to write test data");
+
+    let jump = |extra: &[&str], env: Option<&str>| {
+        let mut command = Command::new(get_binary_path());
+        command
+            .args(extra)
+            .arg("-e")
+            .arg("projects")
+            .current_dir(&projects)
+            .env("_Z_DATA", &temp_data)
+            .env_remove("_Z_EXCLUDE_CWD");
+        if let Some(value) = env {
+            command.env("_Z_EXCLUDE_CWD", value);
+        }
+        let output = command.output().expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+
+    assert_eq!(jump(&[], None), old_projects.to_string_lossy());
+    assert_eq!(jump(&["--include-cwd"], None), projects.to_string_lossy());
+    assert_eq!(jump(&[], Some("0")), projects.to_string_lossy());
+}
+
+#[test]
+fn test_repo_filter() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let root = temp_dir.path().join("api");
+    let deep = root.join("src").join("api");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::create_dir_all(&deep).unwrap();
+    fs::write(
+        &temp_data,
+        format!(
+            "{}|10.0|1640995200\n{}|50.0|1640995200\n",
+            root.display(),
+            deep.display()
+        ),
+    )
+    .expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--repo")
+        .arg("api")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        root.to_string_lossy()
+    );
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--repo-boost")
+        .arg("10")
+        .arg("api")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        root.to_string_lossy()
+    );
+
+    // The flag follows the same rule as _Z_REPO_BOOST
+    for factor in ["-1", "0", "NaN"] {
+        let output = Command::new(get_binary_path())
+            .arg("-l")
+            .arg(format!("--repo-boost={}", factor))
+            .arg("api")
+            .env("_Z_DATA", &temp_data)
+            .output()
+            .expect("Failed to execute command");
+        assert!(!output.status.success(), "{}", factor);
+    }
+}
+
+#[test]
+fn test_repo_scope() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let repo = temp_dir.path().join("repo");
+    let cwd = repo.join("src").join("foo");
+    let docs = repo.join("docs");
+    let other_docs = temp_dir.path().join("other").join("docs");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::create_dir_all(&cwd).unwrap();
+    fs::create_dir_all(&docs).unwrap();
+    fs::create_dir_all(&other_docs).unwrap();
+    let cwd = cwd.canonicalize().unwrap();
+    let docs = docs.canonicalize().unwrap();
+    let other_docs = other_docs.canonicalize().unwrap();
+    fs::write(
+        &temp_data,
+        format!(
+            "{}|5.0|1640995200\n{}|50.0|1640995200\n",
+            docs.display(),
+            other_docs.display()
+        ),
+    )
+    .expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("-c")
+        .arg("docs")
+        .current_dir(&cwd)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--scope")
+        .arg("repo")
+        .arg("docs")
+        .current_dir(&cwd)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        docs.to_string_lossy()
+    );
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("--scope")
+        .arg("repo")
+        .arg("docs")
+        .current_dir(temp_dir.path())
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("no repository root above"));
+}
+
+#[test]
+fn test_proximity_explain() {
+    let temp_data = create_temp_data_file();
+
+    let current_dir = env::current_dir().unwrap().to_string_lossy().to_string();
+    let near = format!("{}/docs", current_dir);
+    fs::write(
+        &temp_data,
+        format!(
+            "/elsewhere/docs|12.0|1640995200\n{}|10.0|1640995200\n",
+            near
+        ),
+    )
+    .expect("Failed to write test data");
+
+    let output = Command::new(get_binary_path())
+        .arg("-e")
+        .arg("docs")
+        .env("_Z_DATA", &temp_data)
+        .env_remove("_Z_PROXIMITY")
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/elsewhere/docs"
+    );
+
+    let output = Command::new(get_binary_path())
+        .arg("--explain")
+        .arg("--proximity")
+        .arg("1")
+        .arg("docs")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert!(explanation.contains(&format!("winner: {}", near)));
+    assert!(explanation.contains("boost x2.00: shares"));
+    assert!(explanation.contains(&format!("path components with {}", current_dir)));
+
+    // Cleanup
+    fs::remove_file(&temp_data).ok();
+}
+
+#[test]
+fn test_visit_histogram_sidecar() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let sidecar = temp_dir.path().join("z.hist");
+    fs::write(&temp_data, "/home/user/old|5.0|1640995200\n").expect("Failed to write test data");
+
+    for _ in 0..2 {
+        let output = Command::new(get_binary_path())
+            .arg("--add")
+            .env("_Z_DATA", &temp_data)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+    }
+
+    let current_dir = env::current_dir().unwrap().to_string_lossy().to_string();
+    let data = fs::read_to_string(&temp_data).unwrap();
+    assert!(data.contains("/home/user/old|5|1640995200"));
+    assert!(data.contains(&format!("{}|2|", current_dir)));
+    let histogram = fs::read_to_string(&sidecar).unwrap();
+    assert!(histogram.starts_with(&format!("{}|", current_dir)));
+
+    let output = Command::new(get_binary_path())
+        .arg("--explain")
+        .arg("--scorer")
+        .arg("routine")
+        .arg("--include-cwd")
+        .arg(&current_dir)
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("1.00 of visits around"));
+}
+
+#[test]
+fn test_stats() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    fs::write(&temp_data, "/home/user/old|40.0|1640995200\n").expect("Failed to write test data");
+
+    for _ in 0..3 {
+        Command::new(get_binary_path())
+            .arg("--add")
+            .env("_Z_DATA", &temp_data)
+            .output()
+            .expect("Failed to execute command");
+    }
+
+    let output = Command::new(get_binary_path())
+        .arg("stats")
+        .env("_Z_DATA", &temp_data)
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let stats = String::from_utf8(output.stdout).unwrap();
+    let current_dir = env::current_dir().unwrap().to_string_lossy().to_string();
+    assert!(stats.contains("entries:       2"));
+    assert!(stats.contains("visits:        3 across 1 entries with history"));
+    let lines: Vec<&str> = stats.lines().collect();
+    let most_visited = lines
+        .iter()
+        .position(|line| *line == "most visited:")
+        .unwrap();
+    assert!(lines[most_visited + 2].trim_start().starts_with("3 "));
+    assert!(lines[most_visited + 2].ends_with(&current_dir));
+    assert!(lines[most_visited + 3].ends_with("/home/user/old"));
+}
+
+#[test]
+fn test_pinned_alias() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let config = temp_dir.path().join("config");
+    fs::create_dir_all(&config).unwrap();
+    let config = config.canonicalize().unwrap();
+    fs::write(
+        &temp_data,
+        format!(
+            "{}|1.0|1640995200\n/srv/prod-logs|90.0|1640995200\n",
+            config.display()
+        ),
+    )
+    .expect("Failed to write test data");
+
+    assert!(zjyo(temp_dir.path(), &["pin", "prod", "config"])
+        .status
+        .success());
+    let datafile = fs::read_to_string(&temp_data).unwrap();
+    assert!(datafile.lines().all(|line| line.split('|').count() == 3));
+
+    let output = zjyo(temp_dir.path(), &["-e", "prod"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        config.to_string_lossy()
+    );
+
+    let output = zjyo(temp_dir.path(), &["-l", "prod"]);
+    let listing = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("pinned"));
+    assert!(lines[0].ends_with("  [pin: prod]"));
+    assert!(lines[1].ends_with("/srv/prod-logs"));
+
+    assert!(zjyo(temp_dir.path(), &["unpin", "prod"]).status.success());
+    assert!(!zjyo(temp_dir.path(), &["unpin", "prod"]).status.success());
+    let output = zjyo(temp_dir.path(), &["-e", "prod"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "/srv/prod-logs"
+    );
+}
+
+#[test]
+fn test_tags() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let api = temp_dir.path().join("acme").join("api");
+    fs::create_dir_all(&api).unwrap();
+    let api = api.canonicalize().unwrap();
+    fs::write(&temp_data, "/srv/globex/api|50.0|1640995200\n").expect("Failed to write test data");
+
+    // Tagging an untracked directory adds it
+    assert!(zjyo(
+        temp_dir.path(),
+        &["tag", "@acme", "client", "-p", "acme/api"]
+    )
+    .status
+    .success());
+    let datafile = fs::read_to_string(&temp_data).unwrap();
+    assert!(datafile.contains(&format!("{}|1|", api.display())));
+    // without counting a visit
+    assert!(!temp_dir.path().join("z.hist").exists());
+
+    let output = zjyo(temp_dir.path(), &["-e", "@acme api"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        api.to_string_lossy()
+    );
+
+    let output = zjyo(temp_dir.path(), &["-l", "api"]);
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.contains(&format!("{}  [@acme @client]", api.display())));
+
+    let output = zjyo(temp_dir.path(), &["tags"]);
+    let tags = String::from_utf8(output.stdout).unwrap();
+    assert!(tags.contains("@acme"));
+    assert!(tags.contains("@client"));
+
+    assert!(zjyo(temp_dir.path(), &["untag", "acme", "-p", "acme/api"])
+        .status
+        .success());
+    let output = zjyo(temp_dir.path(), &["-e", "@acme api"]);
+    assert!(!output.status.success());
+    // Aging a full database doesn't drop a freshly tagged directory
+    let web = temp_dir.path().join("acme").join("web");
+    fs::create_dir_all(&web).unwrap();
+    fs::write(&temp_data, "/some/big|9000|1640995200\n").expect("Failed to write test data");
+    let output = zjyo(temp_dir.path(), &["tag", "acme", "-p", "acme/web"]);
+    assert!(output.status.success());
+    let datafile = fs::read_to_string(&temp_data).unwrap();
+    assert!(datafile.contains("/some/big|9000|"));
+    assert!(datafile.contains(&format!("{}|1|", web.canonicalize().unwrap().display())));
+}
+
+#[test]
+fn test_manual_weight() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let noisy = temp_dir.path().join("node_modules").join("lib");
+    fs::create_dir_all(&noisy).unwrap();
+    let noisy = noisy.canonicalize().unwrap();
+    fs::write(
+        &temp_data,
+        format!(
+            "{}|50.0|1640995200\n/srv/lib|5.0|1640995200\n",
+            noisy.display()
+        ),
+    )
+    .expect("Failed to write test data");
+
+    let echo = |pattern: &str| {
+        String::from_utf8(zjyo(temp_dir.path(), &["-e", pattern]).stdout)
+            .unwrap()
+            .trim()
+            .to_string()
+    };
+
+    assert_eq!(echo("lib"), noisy.to_string_lossy());
+    assert!(zjyo(
+        temp_dir.path(),
+        &["weight", "0.01", "-p", "node_modules/lib"]
+    )
+    .status
+    .success());
+    assert_eq!(echo("lib"), "/srv/lib");
+    assert!(
+        !zjyo(temp_dir.path(), &["weight", "-1", "-p", "node_modules/lib"])
+            .status
+            .success()
+    );
+
+    let output = zjyo(temp_dir.path(), &["weight"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        format!("0.01       {}", noisy.display())
+    );
+
+    let output = zjyo(temp_dir.path(), &["--explain", "lib"]);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("penalty x0.01: manual weight"));
+
+    assert!(
+        zjyo(temp_dir.path(), &["weight", "1", "-p", "node_modules/lib"])
+            .status
+            .success()
+    );
+    assert_eq!(echo("lib"), noisy.to_string_lossy());
+
+    // Weighting an untracked directory in a full database stores the weight
+    fs::write(&temp_data, "/some/big|9000|1640995200\n").expect("Failed to write test data");
+    assert!(
+        zjyo(temp_dir.path(), &["weight", "3", "-p", "node_modules/lib"])
+            .status
+            .success()
+    );
+    let weights = fs::read_to_string(temp_dir.path().join("z.weights")).unwrap();
+    assert_eq!(weights, format!("{}|3\n", noisy.display()));
+    assert!(fs::read_to_string(&temp_data)
+        .unwrap()
+        .contains(&format!("{}|1|", noisy.display())));
+}
+
+#[test]
+fn test_learned_selections() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let legacy = temp_dir.path().join("web-legacy");
+    let frontend = temp_dir.path().join("frontend").join("web");
+    fs::create_dir_all(&legacy).unwrap();
+    fs::create_dir_all(&frontend).unwrap();
+    let (legacy, frontend) = (legacy.display(), frontend.display());
+    let write_ranks = |legacy_rank: f64, extra: &str| {
+        fs::write(
+            &temp_data,
+            format!(
+                "{}|{}|1640995200\n{}|25.0|1640995200\n{}",
+                legacy, legacy_rank, frontend, extra
+            ),
+        )
+        .expect("Failed to write test data");
+    };
+    write_ranks(20.0, "/srv/gone/web|90.0|1640995200\n");
+
+    let jump = |pattern: &str| {
+        String::from_utf8(zjyo(temp_dir.path(), &["-e", "--learn", "--", pattern]).stdout)
+            .unwrap()
+            .trim()
+            .to_string()
+    };
+
+    // Missing directories are removed instead of being learned
+    assert!(!zjyo(temp_dir.path(), &["-e", "--learn", "web"])
+        .status
+        .success());
+    assert!(!temp_dir.path().join("z.learned").exists());
+
+    // Plain -e only prints the match
+    let output = zjyo(temp_dir.path(), &["-e", "web"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        frontend.to_string()
+    );
+    assert!(!temp_dir.path().join("z.learned").exists());
+
+    for _ in 0..3 {
+        assert_eq!(jump("web"), frontend.to_string());
+    }
+    let output = zjyo(temp_dir.path(), &["learned"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        format!("web             3      {}", frontend)
+    );
+
+    // The legacy directory now has the higher rank, but the learned
+    // selections keep the same and prefix queries on the frontend
+    write_ranks(30.0, "");
+    let output = zjyo(temp_dir.path(), &["--explain", "we"]);
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert!(explanation.contains(&format!("winner: {}", frontend)));
+    assert!(explanation.contains("chosen 3 times for queries starting with \"we\""));
+    assert_eq!(jump("WEB"), frontend.to_string());
+
+    assert!(zjyo(temp_dir.path(), &["learned", "--clear"])
+        .status
+        .success());
+    assert!(!temp_dir.path().join("z.learned").exists());
+    assert_eq!(jump("web"), legacy.to_string());
+}
+
+#[test]
+fn test_dwell_time() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+
+    let leave = |args: &[&str], min_dwell: &str| {
+        zjyo_command(temp_dir.path())
+            .args(args)
+            .env("_Z_MIN_DWELL", min_dwell)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    assert!(leave(&["--leave", "/work/quick", "--dwell", "3"], "5")
+        .status
+        .success());
+    assert!(leave(&["--leave", "/work/long", "--dwell", "900"], "5")
+        .status
+        .success());
+    assert!(leave(&["--leave", "/work/quick", "--dwell", "3"], "0")
+        .status
+        .success());
+
+    let content = fs::read_to_string(&temp_data).unwrap();
+    assert!(content.contains("/work/long|3|"), "{}", content);
+    assert!(content.contains("/work/quick|0.25|"), "{}", content);
+
+    // --dwell is required
+    assert!(!leave(&["--leave", "/work/long"], "5").status.success());
+}
+
+#[test]
+fn test_half_life_decay() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_data = temp_dir.path().join("z");
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    fs::write(&temp_data, format!("/srv/old|16|{}\n", now)).unwrap();
+    let mut decay_file = temp_data.clone().into_os_string();
+    decay_file.push(".decay");
+    fs::write(&decay_file, format!("{}\n", now - 2 * 24 * 60 * 60)).unwrap();
+
+    let output = zjyo_command(temp_dir.path())
+        .arg("--add")
+        .env("_Z_HALF_LIFE", "1")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let content = fs::read_to_string(&temp_data).unwrap();
+    let rank: f64 = content
+        .lines()
+        .find_map(|line| line.strip_prefix("/srv/old|"))
+        .and_then(|rest| rest.split('|').next())
+        .and_then(|rank| rank.parse().ok())
+        .unwrap();
+    assert!((rank - 4.0).abs() < 0.01, "{}", content);
+}